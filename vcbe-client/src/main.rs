@@ -12,7 +12,7 @@ fn main() {
         "script" => script(),
         _ => {}
     }
-    let session = std_start(&client);
+    std_start(&client);
 }
 
 fn cli() {
@@ -76,6 +76,7 @@ fn cli_rcl() {
         println!("?\t{}", state.question);
         println!("y\tYes");
         println!("n\tNo");
+        println!("u\tNot sure");
         if state.result_available {
             println!("x\tFinish");
         }
@@ -88,6 +89,7 @@ fn cli_rcl() {
                 "y" => break 0,
                 "n" => break 1,
                 "x" if state.result_available => break 2,
+                "u" => break 3,
                 _ => continue,
             }
         };
@@ -98,6 +100,9 @@ fn cli_rcl() {
             1 => {
                 let _ = rcl_submit(&client, session, RclSubmit::Choose(false));
             }
+            3 => {
+                let _ = rcl_submit(&client, session, RclSubmit::Unsure);
+            }
             2 => {
                 let result = rcl_submit(&client, session, RclSubmit::Finish);
                 match result {
@@ -139,16 +144,16 @@ fn script() {
         }
         "rcl" => {
             let session = rcl_start(&client);
-            let mut last = RclSubmitResult::NotEnough;
             for line in lines {
                 let choice = match line.trim() {
                     "y" => RclSubmit::Choose(true),
                     "n" => RclSubmit::Choose(false),
+                    "u" => RclSubmit::Unsure,
                     _ => unreachable!(),
                 };
-                last = rcl_submit(&client, session, choice);
+                rcl_submit(&client, session, choice);
             }
-            last = rcl_submit(&client, session, RclSubmit::Finish);
+            let last = rcl_submit(&client, session, RclSubmit::Finish);
             if matches!(last, RclSubmitResult::NotEnough) {
                 eprintln!("Not enough questions answered.");
             }
@@ -281,6 +286,7 @@ fn rcl_state(client: &Client, session: u32) -> RclState {
 
 enum RclSubmit {
     Choose(bool),
+    Unsure,
     Finish,
 }
 
@@ -300,6 +306,10 @@ fn rcl_submit(client: &Client, session: u32, action: RclSubmit) -> RclSubmitResu
             ("action".to_string(), "choose".to_string()),
             ("recall".to_string(), recall.to_string()),
         ]),
+        RclSubmit::Unsure => HashMap::from([
+            ("action".to_string(), "choose".to_string()),
+            ("recall".to_string(), "unsure".to_string()),
+        ]),
         RclSubmit::Finish => HashMap::from([
             ("action".to_string(), "finish".to_string()),
         ])
@@ -312,7 +322,7 @@ fn rcl_submit(client: &Client, session: u32, action: RclSubmit) -> RclSubmitResu
         .send().unwrap()
         .json().unwrap();
     match action {
-        RclSubmit::Choose(_) => RclSubmitResult::Choose,
+        RclSubmit::Choose(_) | RclSubmit::Unsure => RclSubmitResult::Choose,
         RclSubmit::Finish => {
             if resp.details.get("error") == Some(&"not enough questions answered".to_string()) {
                 return RclSubmitResult::NotEnough;
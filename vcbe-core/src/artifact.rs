@@ -0,0 +1,86 @@
+use std::fmt;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use crate::{Bands, Metadata};
+
+/// Leading bytes of every dictionary artifact
+pub const MAGIC: [u8; 4] = *b"VCBE";
+
+/// Version of the artifact layout written by this build, version 1 being the bare
+/// MessagePack lists written before artifacts had a header
+pub const SCHEMA_VERSION: u16 = 2;
+
+const HEADER_LEN: usize = MAGIC.len() + 2 + 8;
+
+/// A generated dictionary artifact such as the rows or the entries, carrying the band
+/// definitions and metadata it was generated with
+#[derive(serde::Serialize, serde:: Deserialize)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Artifact<T> {
+    pub meta: Metadata,
+    pub bands: Bands,
+    pub items: Vec<T>,
+}
+
+#[derive(Debug)]
+pub enum ArtifactError {
+    /// Written by a newer build
+    Version(u16),
+    Checksum,
+    Malformed(String),
+}
+
+impl fmt::Display for ArtifactError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArtifactError::Version(x) => write!(f, "unsupported artifact version {}", x),
+            ArtifactError::Checksum => write!(f, "artifact checksum mismatch"),
+            ArtifactError::Malformed(x) => write!(f, "malformed artifact: {}", x),
+        }
+    }
+}
+
+impl std::error::Error for ArtifactError {}
+
+/// 64-bit FNV-1a hash of the payload
+fn checksum(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |acc, x| (acc ^ *x as u64).wrapping_mul(0x100000001b3))
+}
+
+impl<T: Serialize> Artifact<T> {
+    /// Magic bytes, little-endian version and checksum, then the artifact as MessagePack with
+    /// field names so that added fields do not shift the others
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let payload = rmp_serde::to_vec_named(self).unwrap();
+        let mut bytes = Vec::with_capacity(HEADER_LEN + payload.len());
+        bytes.extend_from_slice(&MAGIC);
+        bytes.extend_from_slice(&SCHEMA_VERSION.to_le_bytes());
+        bytes.extend_from_slice(&checksum(&payload).to_le_bytes());
+        bytes.extend_from_slice(&payload);
+        bytes
+    }
+}
+
+impl<T: DeserializeOwned> Artifact<T> {
+    /// Reads an artifact of any known version, older ones getting the default metadata and
+    /// bands
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ArtifactError> {
+        let malformed = |x: rmp_serde::decode::Error| ArtifactError::Malformed(x.to_string());
+        if !bytes.starts_with(&MAGIC) {
+            let items = rmp_serde::from_slice(bytes).map_err(malformed)?;
+            return Ok(Artifact { meta: Metadata::default(), bands: Bands::default(), items });
+        }
+        if bytes.len() < HEADER_LEN {
+            return Err(ArtifactError::Malformed("truncated header".to_string()));
+        }
+        let version = u16::from_le_bytes(bytes[4..6].try_into().unwrap());
+        if version > SCHEMA_VERSION {
+            return Err(ArtifactError::Version(version));
+        }
+        let payload = &bytes[HEADER_LEN..];
+        if checksum(payload) != u64::from_le_bytes(bytes[6..HEADER_LEN].try_into().unwrap()) {
+            return Err(ArtifactError::Checksum);
+        }
+        rmp_serde::from_slice(payload).map_err(malformed)
+    }
+}
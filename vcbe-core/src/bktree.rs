@@ -0,0 +1,114 @@
+use crate::edit_distance as distance;
+
+struct Node {
+    id: usize,
+    /// Children keyed by their distance to this node
+    children: Vec<(usize, usize)>,
+}
+
+/// Burkhard-Keller tree over words, answering which words lie within a distance of a query
+/// without comparing it to every word
+pub struct BkTree {
+    words: Vec<Vec<char>>,
+    nodes: Vec<Node>,
+}
+
+impl BkTree {
+    pub fn new(words: Vec<Vec<char>>) -> Self {
+        let mut tree = BkTree { nodes: Vec::with_capacity(words.len()), words };
+        (0..tree.words.len()).for_each(|id| tree.insert(id));
+        tree
+    }
+
+    pub fn len(&self) -> usize {
+        self.words.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+
+    pub fn word(&self, id: usize) -> &[char] {
+        &self.words[id]
+    }
+
+    fn insert(&mut self, id: usize) {
+        if self.nodes.is_empty() {
+            self.nodes.push(Node { id, children: Vec::new() });
+            return;
+        }
+        let mut current = 0;
+        loop {
+            let d = distance(&self.words[self.nodes[current].id], &self.words[id]);
+            match self.nodes[current].children.iter().find(|x| x.0 == d) {
+                Some(&(_, child)) => current = child,
+                None => {
+                    let child = self.nodes.len();
+                    self.nodes.push(Node { id, children: Vec::new() });
+                    self.nodes[current].children.push((d, child));
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Ids of the words within `max` of `word` with their distances, closest first and
+    /// lower ids first among equally close ones
+    pub fn find(&self, word: &[char], max: usize) -> Vec<(usize, usize)> {
+        let mut found = Vec::new();
+        let mut stack = if self.nodes.is_empty() { vec![] } else { vec![0] };
+        while let Some(current) = stack.pop() {
+            let node = &self.nodes[current];
+            let d = distance(&self.words[node.id], word);
+            if d <= max {
+                found.push((node.id, d));
+            }
+            // by the triangle inequality, only children this close can hold matches
+            stack.extend(node.children.iter()
+                .filter(|x| x.0 + max >= d && x.0 <= d + max)
+                .map(|x| x.1));
+        }
+        found.sort_unstable_by_key(|x| (x.1, x.0));
+        found
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WORDS: [&str; 12] = [
+        "book", "books", "boot", "brook", "cook", "look", "took", "bookcase",
+        "back", "a", "", "kooks",
+    ];
+
+    /// Every word within `max` of `word` by comparing it with all of them
+    fn brute(words: &[Vec<char>], word: &[char], max: usize) -> Vec<(usize, usize)> {
+        let mut found = words.iter()
+            .enumerate()
+            .map(|(i, x)| (i, distance(x, word)))
+            .filter(|x| x.1 <= max)
+            .collect::<Vec<_>>();
+        found.sort_unstable_by_key(|x| (x.1, x.0));
+        found
+    }
+
+    #[test]
+    fn find_matches_brute_force() {
+        let words = WORDS.iter().map(|x| x.chars().collect()).collect::<Vec<Vec<char>>>();
+        let tree = BkTree::new(words.clone());
+        let queries = words.iter().cloned().chain(["bok", "xyz"].map(|x| x.chars().collect()));
+        for query in queries {
+            for max in 0..=4 {
+                assert_eq!(tree.find(&query, max), brute(&words, &query, max),
+                           "{:?} within {}", query, max);
+            }
+        }
+    }
+
+    #[test]
+    fn find_in_empty_tree() {
+        let tree = BkTree::new(Vec::new());
+        assert!(tree.find(&['a'], 3).is_empty());
+    }
+}
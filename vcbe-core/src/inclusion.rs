@@ -0,0 +1,65 @@
+use std::cmp::Reverse;
+
+/// Suffix array over all words, finding the words that contain a given string without
+/// scanning the whole dictionary
+#[derive(Debug, Clone)]
+pub struct InclusionIndex {
+    /// Words joined by NUL, which no word contains
+    text: String,
+    /// Byte offset of each word in `text`
+    starts: Vec<u32>,
+    /// Byte offsets of the suffixes of `text` in lexicographical order
+    suffixes: Vec<u32>,
+    freq: Vec<u32>,
+}
+
+impl InclusionIndex {
+    /// Indexes `words`, `freq` being their frequencies used for ranking
+    pub fn new<S: AsRef<str>>(words: &[S], freq: &[u32]) -> Self {
+        let mut text = String::new();
+        let mut starts = Vec::with_capacity(words.len());
+        for word in words {
+            starts.push(text.len() as u32);
+            text.push_str(word.as_ref());
+            text.push('\0');
+        }
+        // suffixes starting inside a character or at a separator never match a query
+        let mut suffixes = text.char_indices()
+            .filter(|x| x.1 != '\0')
+            .map(|x| x.0 as u32)
+            .collect::<Vec<_>>();
+        suffixes.sort_unstable_by(|x, y| text[*x as usize..].cmp(&text[*y as usize..]));
+        InclusionIndex { text, starts, suffixes, freq: freq.to_vec() }
+    }
+
+    pub fn len(&self) -> usize {
+        self.starts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.starts.is_empty()
+    }
+
+    pub fn word(&self, id: usize) -> &str {
+        let end = self.text[self.starts[id] as usize..].find('\0').unwrap();
+        &self.text[self.starts[id] as usize..self.starts[id] as usize + end]
+    }
+
+    /// Ids of the words longer than `part` containing it, most frequent first and at most `cap`
+    /// of them
+    pub fn containing(&self, part: &str, cap: usize) -> Vec<usize> {
+        if part.is_empty() { return Vec::new(); }
+        let suffix = |x: &u32| &self.text[*x as usize..];
+        let begin = self.suffixes.partition_point(|x| suffix(x) < part);
+        let end = begin + self.suffixes[begin..].partition_point(|x| suffix(x).starts_with(part));
+        let mut ids = self.suffixes[begin..end].iter()
+            .map(|x| self.starts.partition_point(|y| y <= x) - 1)
+            .filter(|x| self.word(*x).len() > part.len())
+            .collect::<Vec<_>>();
+        ids.sort_unstable();
+        ids.dedup();
+        ids.sort_by_key(|x| (Reverse(self.freq[*x]), *x));
+        ids.truncate(cap);
+        ids
+    }
+}
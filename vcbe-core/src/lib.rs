@@ -1,14 +1,224 @@
+mod artifact;
+mod bktree;
+mod inclusion;
+mod mapped;
+
 use std::collections::HashMap;
+use std::fmt;
 use std::ops::Range;
 #[cfg(feature = "tyv")]
 use tch::{CModule, Tensor};
 
+pub use artifact::{Artifact, ArtifactError, MAGIC, SCHEMA_VERSION};
+pub use bktree::BkTree;
+pub use inclusion::InclusionIndex;
+#[cfg(feature = "mmap")]
+pub use mapped::MappedDict;
+pub use mapped::{DictView, MAPPED_MAGIC, MAPPED_VERSION};
+
 pub const LV_RANGES: [Range<u32>; 8] = [
     0..1023, 1023..2925, 2925..6520, 6520..13082,
     13082..23333, 23333..36945, 36945..49245, 49245..68178
 ];
 pub const LV_COUNTS: [usize; 8] = [1023, 1902, 3595, 6562, 10251, 13612, 12300, 18933];
 
+/// Description of a dictionary, languages are given as BCP 47 tags such as "en" or "zh"
+#[derive(serde::Serialize, serde:: Deserialize)]
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct Metadata {
+    pub name: String,
+    /// Language of the headwords
+    pub source: String,
+    /// Language of the definitions
+    pub gloss: String,
+}
+
+impl Default for Metadata {
+    fn default() -> Self {
+        Metadata {
+            name: DEFAULT_DICTIONARY.to_string(),
+            source: "en".to_string(),
+            gloss: "zh".to_string(),
+        }
+    }
+}
+
+/// Name of the dictionary served when none is asked for
+pub const DEFAULT_DICTIONARY: &str = "default";
+
+/// Name of a table of the dictionary, the default one keeps the bare table names while the
+/// others get theirs suffixed with the dictionary name
+pub fn dictionary_table(base: &str, dictionary: &str) -> String {
+    if dictionary == DEFAULT_DICTIONARY {
+        base.to_string()
+    } else {
+        format!("{}_{}", base, dictionary)
+    }
+}
+
+/// Level bands over a dictionary sorted by descending frequency
+#[derive(serde::Serialize, serde:: Deserialize)]
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct Bands {
+    pub ranges: Vec<Range<u32>>,
+}
+
+/// Ways to divide a dictionary sorted by descending frequency into bands
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum Banding {
+    /// Fixed number of words in each band, the last band takes the remainder
+    Count(Vec<usize>),
+    /// Equal number of words in each band
+    Quantile(usize),
+    /// Equal share of the cumulative frequency in each band
+    Mass(usize),
+}
+
+impl Default for Bands {
+    fn default() -> Self {
+        Bands { ranges: LV_RANGES.to_vec() }
+    }
+}
+
+impl Bands {
+    /// Computes bands over `freq`, which must be sorted in descending order
+    pub fn compute(banding: &Banding, freq: &[u32]) -> Bands {
+        let len = freq.len();
+        let mut ends = match banding {
+            Banding::Count(counts) => counts.iter()
+                .scan(0, |acc, x| { *acc += x; Some((*acc).min(len)) })
+                .collect::<Vec<_>>(),
+            Banding::Quantile(n) => (1..=*n)
+                .map(|i| len * i / n)
+                .collect(),
+            Banding::Mass(n) => {
+                let total = freq.iter().map(|x| *x as u64).sum::<u64>();
+                let mut cumulative = 0;
+                let mut ends = Vec::with_capacity(*n);
+                for (i, x) in freq.iter().enumerate() {
+                    cumulative += *x as u64;
+                    if ends.len() < *n && cumulative * *n as u64 >= total * (ends.len() as u64 + 1) {
+                        ends.push(i + 1);
+                    }
+                }
+                ends
+            }
+        };
+        // the last band always reaches the end of the dictionary
+        match ends.last_mut() {
+            Some(last) => *last = len,
+            None => ends.push(len),
+        }
+        let mut ranges = Vec::with_capacity(ends.len());
+        let mut begin = 0;
+        for end in ends {
+            if end > begin {
+                ranges.push(begin as u32..end as u32);
+                begin = end;
+            }
+        }
+        Bands { ranges }
+    }
+
+    pub fn len(&self) -> usize {
+        self.ranges.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    /// Number of words in the level
+    pub fn count(&self, lv: usize) -> usize {
+        self.ranges[lv].len()
+    }
+
+    /// Number of words in all levels
+    pub fn total(&self) -> usize {
+        self.ranges.last().map(|x| x.end as usize).unwrap_or(0)
+    }
+
+    pub fn level_of(&self, id: u32) -> Option<u8> {
+        self.ranges.iter().position(|x| x.contains(&id)).map(|x| x as u8)
+    }
+}
+
+/// A single sense of a word with its part of speech, which is empty when unknown
+#[derive(serde::Serialize, serde:: Deserialize)]
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[serde(from = "DefinitionRepr")]
+pub struct Definition {
+    pub pos: String,
+    pub text: String,
+}
+
+/// Definitions used to be stored as "kind. text" strings, MessagePack stores the parsed ones
+/// as arrays by default
+#[derive(serde:: Deserialize)]
+#[serde(untagged)]
+enum DefinitionRepr {
+    Text(String),
+    Pair(String, String),
+    Parsed { pos: String, text: String },
+}
+
+impl From<DefinitionRepr> for Definition {
+    fn from(value: DefinitionRepr) -> Self {
+        match value {
+            DefinitionRepr::Text(desc) => Definition::parse(&desc),
+            DefinitionRepr::Pair(pos, text) | DefinitionRepr::Parsed { pos, text } =>
+                Definition { pos, text },
+        }
+    }
+}
+
+impl Definition {
+    /// Parses a definition in the "kind. text" form
+    pub fn parse(desc: &str) -> Definition {
+        match desc.split_once('.') {
+            Some((pos, text)) if is_pos(pos.trim()) => Definition {
+                pos: pos.trim().to_string(),
+                text: text.trim().to_string(),
+            },
+            _ => Definition {
+                pos: String::new(),
+                text: desc.trim().to_string(),
+            },
+        }
+    }
+
+    /// Broad class of the part of speech, so that e.g. "vt" and "vi" are both verbs
+    pub fn pos_class(&self) -> &str {
+        match self.pos.as_str() {
+            "v" | "vt" | "vi" | "aux" => "v",
+            "a" | "adj" => "adj",
+            "ad" | "adv" => "adv",
+            pos => pos,
+        }
+    }
+}
+
+/// Part-of-speech abbreviations definitions are marked with, anything else before a '.' is
+/// part of the text, as in "e.g." or "U.S."
+const POS: [&str; 18] = [
+    "n", "v", "vt", "vi", "aux", "a", "adj", "ad", "adv", "prep", "conj", "pron", "num", "art",
+    "int", "interj", "abbr", "pl",
+];
+
+fn is_pos(pos: &str) -> bool {
+    POS.contains(&pos)
+}
+
+impl fmt::Display for Definition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.pos.is_empty() {
+            write!(f, "{}", self.text)
+        } else {
+            write!(f, "{}. {}", self.pos, self.text)
+        }
+    }
+}
+
 #[derive(serde::Serialize, serde:: Deserialize)]
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
 pub struct Word {
@@ -19,7 +229,7 @@ pub struct Word {
     pub p_us: String,
     pub p_uk: String,
     pub exam: Vec<String>,
-    pub desc: Vec<String>,
+    pub desc: Vec<Definition>,
     pub phr: Vec<String>,
     pub phr_desc: Vec<String>,
     pub sen: Vec<String>,
@@ -36,7 +246,7 @@ pub struct Entry {
     pub p_us: String,
     pub p_uk: String,
     pub exam: Vec<String>,
-    pub desc: Vec<String>,
+    pub desc: Vec<Definition>,
     pub phr: Vec<String>,
     pub phr_desc: Vec<String>,
     pub sen: Vec<String>,
@@ -45,19 +255,88 @@ pub struct Entry {
     pub sim: Vec<usize>,
     pub incl: Vec<usize>,
     pub incl_rev: Vec<usize>,
+    /// Words of similar pronunciation, closest first
+    #[serde(default)]
+    pub sound: Vec<usize>,
 }
 
 #[derive(serde::Serialize, serde:: Deserialize)]
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[serde(from = "RowRepr")]
 pub struct Row {
     pub id: usize,
     pub word: String,
     pub freq: u32,
-    pub desc: Vec<String>,
+    pub desc: Vec<Definition>,
     pub lv: u8,
     pub sim: Vec<usize>,
     pub incl: Vec<usize>,
     pub incl_rev: Vec<usize>,
+    pub exam: Vec<String>,
+    pub head: String,
+    pub sound: Vec<usize>,
+    /// Id of the most frequent member of the word family
+    pub family: usize,
+}
+
+/// Rows written before families were collected make each word its own family
+#[derive(serde:: Deserialize)]
+struct RowRepr {
+    id: usize,
+    word: String,
+    freq: u32,
+    desc: Vec<Definition>,
+    lv: u8,
+    sim: Vec<usize>,
+    incl: Vec<usize>,
+    incl_rev: Vec<usize>,
+    #[serde(default)]
+    exam: Vec<String>,
+    #[serde(default)]
+    head: String,
+    #[serde(default)]
+    sound: Vec<usize>,
+    #[serde(default)]
+    family: Option<usize>,
+}
+
+impl From<RowRepr> for Row {
+    fn from(value: RowRepr) -> Self {
+        Row {
+            id: value.id,
+            word: value.word,
+            freq: value.freq,
+            desc: value.desc,
+            lv: value.lv,
+            sim: value.sim,
+            incl: value.incl,
+            incl_rev: value.incl_rev,
+            exam: value.exam,
+            head: value.head,
+            sound: value.sound,
+            family: value.family.unwrap_or(value.id),
+        }
+    }
+}
+
+/// The word a family is grouped under, the headword if there is one
+pub fn family_root<'a>(word: &'a str, head: &'a str) -> &'a str {
+    if head.is_empty() { word } else { head }
+}
+
+/// Levenshtein distance between two words, counted in characters
+pub fn edit_distance(a: &[char], b: &[char]) -> usize {
+    let mut row = (0..=b.len()).collect::<Vec<_>>();
+    for (i, x) in a.iter().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, y) in b.iter().enumerate() {
+            let substitution = diagonal + (x != y) as usize;
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
 }
 
 #[derive(serde::Serialize, serde:: Deserialize)]
@@ -68,50 +347,103 @@ pub struct Message {
 }
 
 #[derive(serde::Serialize, serde:: Deserialize)]
-#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[derive(Debug, Copy, Clone, PartialEq)]
 pub struct Evidence {
     pub id: usize,
     pub freq: u32,
     pub lv: u8,
     pub correct: bool,
+    /// Partial credit in [0, 1], overrides `correct` when present
+    #[serde(default)]
+    pub score: Option<f64>,
+    /// Response time in milliseconds
+    #[serde(default)]
+    pub time: Option<u32>,
+}
+
+/// Partial credit is counted in millionths when weighting, keeping weighted sums in integers
+const CREDIT_PARTS: u128 = 1_000_000;
+
+impl Evidence {
+    pub fn credit(&self) -> f64 {
+        self.score
+            .unwrap_or(if self.correct { 1.0 } else { 0.0 })
+            .clamp(0.0, 1.0)
+    }
+
+    /// `weight` scaled by the credit in integers, exact for full and no credit
+    pub fn weighted(&self, weight: u128) -> u128 {
+        match self.score {
+            None => self.correct as u128 * weight,
+            Some(_) => {
+                let parts = (self.credit() * CREDIT_PARTS as f64).round() as u128;
+                weight * parts / CREDIT_PARTS
+            }
+        }
+    }
 }
 
 /// Uniform leveled scaling
-pub fn estimate_uls(evidences: Vec<Evidence>) -> usize {
+pub fn estimate_uls(evidences: Vec<Evidence>, bands: &Bands) -> usize {
     // count the number of answers & ~ of correct answers for each level
-    let ratios = evidences.iter().fold([(0, 0); 8], |mut acc, x| {
-        acc[x.lv as usize].0 += 1u32;
-        acc[x.lv as usize].1 += x.correct as usize;
+    let ratios = evidences.iter().fold(vec![(0u32, 0.0); bands.len()], |mut acc, x| {
+        acc[x.lv as usize].0 += 1;
+        acc[x.lv as usize].1 += x.credit();
         acc
     });
     let mut estimate = 0u32;
     // assume every word can represent each other within the same level
     for (i, (total, correct)) in ratios.iter().enumerate() {
         if *total == 0 { continue; }
-        estimate += (LV_COUNTS[i] * *correct / *total as usize) as u32;
+        estimate += (bands.count(i) as f64 * *correct / *total as f64) as u32;
     }
     estimate as usize
 }
 
+/// Answers given on a level and the words of the level estimated known
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub struct LevelResult {
+    pub asked: usize,
+    pub correct: usize,
+    /// Extrapolated from the credit of the answers as in ULS
+    pub known: usize,
+}
+
+/// Breakdown of a result by level
+pub fn level_results(evidences: &[Evidence], bands: &Bands) -> Vec<LevelResult> {
+    let mut levels = vec![LevelResult::default(); bands.len()];
+    let mut credits = vec![0.0; bands.len()];
+    for x in evidences {
+        levels[x.lv as usize].asked += 1;
+        levels[x.lv as usize].correct += x.correct as usize;
+        credits[x.lv as usize] += x.credit();
+    }
+    for (i, (level, credit)) in levels.iter_mut().zip(credits).enumerate() {
+        if level.asked == 0 { continue; }
+        level.known = (bands.count(i) as f64 * credit / level.asked as f64) as usize;
+    }
+    levels
+}
+
 /// Reciprocal frequency weighted leveled scaling
-pub fn estimate_rfwls(evidences: Vec<Evidence>) -> usize {
+pub fn estimate_rfwls(evidences: Vec<Evidence>, bands: &Bands) -> usize {
     let one = u128::MAX / 1000_0000;
     // same as ULS but weight with the reciprocal of frequency
-    let ratios = evidences.iter().fold([(0, 0); 8], |mut acc, x| {
+    let ratios = evidences.iter().fold(vec![(0, 0); bands.len()], |mut acc, x| {
         let weight = one / x.freq as u128;
         acc[x.lv as usize].0 += weight;
-        acc[x.lv as usize].1 += x.correct as u128 * weight;
+        acc[x.lv as usize].1 += x.weighted(weight);
         acc
     });
     let mut estimate = 0u32;
     for (i, (total, correct)) in ratios.iter().enumerate() {
         if *total == 0 { continue; }
-        estimate += (LV_COUNTS[i] as u128 * *correct / *total) as u32;
+        estimate += (bands.count(i) as u128 * *correct / *total) as u32;
     }
     estimate as usize
 }
 
-/// Maximum likelihood estimation
+// Maximum likelihood estimation
 // pub fn estimate_mle(evidence: Vec<Evidence>, freq: Vec<u32>) -> usize {
 //     let freq_total = *freq.iter().max().unwrap() as f64 / 1000.0;
 //
@@ -148,7 +480,7 @@ pub fn estimate_rfwls(evidences: Vec<Evidence>) -> usize {
 // }
 
 /// Heuristic estimation
-pub fn estimate_heu(evidences: Vec<Evidence>) -> usize {
+pub fn estimate_heu(evidences: Vec<Evidence>, bands: &Bands) -> usize {
     // let max = 30.0;
     // let mut spectrum = [0f64; 68178];
     // for word in evidences {
@@ -168,23 +500,218 @@ pub fn estimate_heu(evidences: Vec<Evidence>) -> usize {
     let total: u128 = evidences.iter()
         .map(|x| one / x.freq as u128).sum();
     let correct: u128 = evidences.iter()
-        .filter(|x| x.correct)
-        .map(|x| one / x.freq as u128).sum();
-    (correct * 68178 / total) as usize
+        .map(|x| x.weighted(one / x.freq as u128)).sum();
+    (correct * bands.total() as u128 / total) as usize
+}
+
+/// Word family size, each tested word standing for its whole family, scaled per level by
+/// `counts`, the number of families whose most frequent member is in the level
+pub fn estimate_families(evidences: &[Evidence], counts: &[usize]) -> usize {
+    let ratios = evidences.iter().fold(vec![(0u32, 0.0); counts.len()], |mut acc, x| {
+        acc[x.lv as usize].0 += 1;
+        acc[x.lv as usize].1 += x.credit();
+        acc
+    });
+    counts.iter().zip(ratios)
+        .filter(|(_, (total, _))| *total > 0)
+        .map(|(count, (total, correct))| (*count as f64 * correct / total as f64) as usize)
+        .sum()
+}
+
+/// Share of a word list known, estimated per level from the answers on listed words where
+/// there are any and from all answers of the level otherwise, `counts` being the number of
+/// listed words in each level
+pub fn estimate_coverage(evidences: &[Evidence], listed: &[Evidence], counts: &[usize]) -> f64 {
+    let ratio = |evidences: &[Evidence], lv: usize| {
+        let level = evidences.iter()
+            .filter(|x| x.lv as usize == lv)
+            .collect::<Vec<_>>();
+        if level.is_empty() { return None; }
+        Some(level.iter().map(|x| x.credit()).sum::<f64>() / level.len() as f64)
+    };
+    let total = counts.iter().sum::<usize>();
+    if total == 0 { return 0.0; }
+    let known = counts.iter().enumerate()
+        .map(|(lv, count)| *count as f64 * ratio(listed, lv)
+            .or_else(|| ratio(evidences, lv))
+            .unwrap_or(0.0))
+        .sum::<f64>();
+    known / total as f64
+}
+
+/// Share of a level answered correctly above which the level is taken as mastered
+pub const MASTERY: f64 = 0.9;
+
+/// Id where the words the learner knows give out: inside the first level that is not
+/// mastered, as far into it as the share answered correctly, the level being ordered by
+/// frequency
+pub fn frontier(evidences: &[Evidence], bands: &Bands) -> u32 {
+    let ratios = evidences.iter().fold(vec![(0u32, 0.0); bands.len()], |mut acc, x| {
+        acc[x.lv as usize].0 += 1;
+        acc[x.lv as usize].1 += x.credit();
+        acc
+    });
+    for (range, (total, correct)) in bands.ranges.iter().zip(ratios) {
+        // nothing known can be said about a level without answers
+        if total == 0 { return range.start; }
+        let ratio = correct / total as f64;
+        if ratio < MASTERY {
+            return range.start + (range.len() as f64 * ratio) as u32;
+        }
+    }
+    bands.ranges.last().map_or(0, |x| x.end)
+}
+
+/// Answers quicker than this many milliseconds are considered implausible
+pub const FAST_THRESHOLD: u32 = 300;
+
+/// Halve the credit of answers given faster than `threshold` milliseconds
+pub fn discount_fast(evidences: Vec<Evidence>, threshold: u32) -> Vec<Evidence> {
+    evidences.into_iter()
+        .map(|x| match x.time {
+            Some(time) if time < threshold => Evidence {
+                score: Some(x.credit() * 0.5),
+                ..x
+            },
+            _ => x,
+        })
+        .collect()
+}
+
+/// False alarm rate from self-reported answers on pseudo-words
+pub fn false_alarm_rate(pseudo: &[f64]) -> f64 {
+    if pseudo.is_empty() { return 0.0; }
+    pseudo.iter().sum::<f64>() / pseudo.len() as f64
+}
+
+/// Hit/false-alarm correction of a self-reported estimate
+pub fn adjust_false_alarm(estimate: usize, total: usize, false_alarm: f64) -> usize {
+    // the ratio of claimed words is `h`, the real one is `(h - f) / (1 - f)`, scaled by `total`
+    if false_alarm >= 1.0 { return 0; }
+    let adjusted = (estimate as f64 - false_alarm * total as f64) / (1.0 - false_alarm);
+    adjusted.max(0.0) as usize
+}
+
+/// Spaced-repetition schedule of a word under SM-2, `interval` is in days
+#[derive(serde::Serialize, serde:: Deserialize)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Review {
+    pub ease: f64,
+    pub interval: u32,
+    pub repetitions: u32,
+}
+
+impl Default for Review {
+    fn default() -> Self {
+        Review { ease: 2.5, interval: 0, repetitions: 0 }
+    }
+}
+
+impl Review {
+    /// Schedule after a review graded from 0 (blackout) to 5 (perfect recall)
+    pub fn grade(self, quality: u8) -> Review {
+        let quality = quality.min(5);
+        let q = (5 - quality) as f64;
+        let ease = (self.ease + 0.1 - q * (0.08 + q * 0.02)).max(1.3);
+        // failed items start over but keep their lowered ease
+        if quality < 3 {
+            return Review { ease, interval: 1, repetitions: 0 };
+        }
+        let interval = match self.repetitions {
+            0 => 1,
+            1 => 6,
+            _ => (self.interval as f64 * self.ease).round() as u32,
+        };
+        Review { ease, interval, repetitions: self.repetitions + 1 }
+    }
+}
+
+/// Most words a learner is taken to plausibly gain or lose in a day
+pub const DAILY_GROWTH: f64 = 50.0;
+
+/// Share of an estimate by which repeated tests may differ on their own
+pub const RETEST_SPREAD: f64 = 0.25;
+
+/// Weight of a new estimate in the smoothed trend
+pub const SMOOTHING: f64 = 0.5;
+
+/// Tests on each side an estimate is checked against
+pub const TREND_NEIGHBORS: usize = 2;
+
+/// Course of the estimates of a learner over repeated tests
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trend {
+    /// Exponentially smoothed estimate after each test
+    pub smoothed: Vec<f64>,
+    /// Whether each estimate jumped further from most of the tests around it than learning
+    /// explains, such estimates are left out of the trend and the growth rate
+    pub jumps: Vec<bool>,
+    /// Words gained per day, the least-squares slope of the plausible estimates
+    pub growth: f64,
+}
+
+/// Trend of `points`, the day of each test and its estimate in the order taken
+pub fn trend(points: &[(f64, f64)]) -> Trend {
+    // an estimate only counts as a jump against a majority of the tests around it, so that a
+    // single outlier, the first test included, does not make the others look like jumps
+    let consistent = |(day, estimate): (f64, f64), (since, value): (f64, f64)| {
+        (estimate - value).abs() <= value * RETEST_SPREAD + DAILY_GROWTH * (day - since).abs()
+    };
+    let jumps = (0..points.len())
+        .map(|i| {
+            let neighbors = points[i.saturating_sub(TREND_NEIGHBORS)..i].iter()
+                .chain(points.iter().skip(i + 1).take(TREND_NEIGHBORS))
+                .copied()
+                .collect::<Vec<_>>();
+            // too few tests to tell which of them is off
+            if neighbors.len() < 2 { return false; }
+            let inconsistent = neighbors.iter().filter(|x| !consistent(points[i], **x)).count();
+            inconsistent * 2 > neighbors.len()
+        })
+        .collect::<Vec<_>>();
+    // jumps before the first plausible estimate hold the trend at it
+    let mut last = points.iter().zip(&jumps).find(|(_, jump)| !**jump).map(|(x, _)| x.1);
+    let smoothed = points.iter().zip(&jumps)
+        .map(|((_, estimate), jump)| {
+            let value = match (jump, last) {
+                (_, None) => *estimate,
+                (true, Some(value)) => value,
+                (false, Some(value)) => value + SMOOTHING * (estimate - value),
+            };
+            if !jump { last = Some(value); }
+            value
+        })
+        .collect::<Vec<_>>();
+    let plausible = points.iter().zip(&jumps)
+        .filter(|(_, jump)| !**jump)
+        .map(|(x, _)| *x)
+        .collect::<Vec<_>>();
+    let n = plausible.len() as f64;
+    let mean_day = plausible.iter().map(|x| x.0).sum::<f64>() / n;
+    let mean_estimate = plausible.iter().map(|x| x.1).sum::<f64>() / n;
+    let covariance = plausible.iter()
+        .map(|x| (x.0 - mean_day) * (x.1 - mean_estimate))
+        .sum::<f64>();
+    let variance = plausible.iter().map(|x| (x.0 - mean_day).powi(2)).sum::<f64>();
+    // tests taken on a single day say nothing about growth
+    let growth = if variance > 0.0 { covariance / variance } else { 0.0 };
+    Trend { smoothed, jumps, growth }
 }
 
 /// Machine learning based mimicry of Test-Your-Vocab scoring
 #[cfg(feature = "tyv")]
-pub fn estimate_tyv(result: &[(&str, bool)], data: &TyvData) -> usize {
+pub fn estimate_tyv(result: &[(&str, f64)], data: &TyvData) -> usize {
     let mut broad = [0.0; 127];
     let mut narrow = [0.0; 608];
-    // in each vector, 1.0 for correct, -1.0 for incorrect, 0.0 for unknown (not tested)
+    // in each vector, 1.0 for correct, -1.0 for incorrect, 0.0 for unknown (not tested),
+    // partial credit falls in between
     for (word, recall) in result {
-        if let Some(i) = data.broad_toi.get(&word.to_string()) {
-            broad[*i] = if *recall { 1.0 } else { -1.0 }
+        let value = (recall.clamp(0.0, 1.0) * 2.0 - 1.0) as f32;
+        if let Some(i) = data.broad_toi.get(*word) {
+            broad[*i] = value;
         }
-        if let Some(i) = data.narrow_toi.get(&word.to_string()) {
-            narrow[*i] = if *recall { 1.0 } else { -1.0 }
+        if let Some(i) = data.narrow_toi.get(*word) {
+            narrow[*i] = value;
         }
     }
     tyv_inference(&data.model, &broad, &narrow) as usize
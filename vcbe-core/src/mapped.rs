@@ -0,0 +1,394 @@
+use std::ops::Range;
+use crate::{ArtifactError, Bands, Definition, Row};
+
+/// Leading bytes of a mapped dictionary
+pub const MAPPED_MAGIC: [u8; 4] = *b"VCBM";
+
+pub const MAPPED_VERSION: u16 = 1;
+
+/// Magic, version, word count, band count and the offsets of the five sections
+const HEADER_LEN: usize = 4 + 2 + 2 + 4 + 4 + 5 * 8;
+
+/// Fixed-width word record, all integers little-endian: word, head, desc and exam as
+/// (offset, length) into the string table, freq, lv (padded to four bytes), family, the offset
+/// of the relations of the word into the relation array, then the lengths of sim, incl,
+/// incl_rev and sound as two bytes each
+const RECORD_LEN: usize = 8 * 4 + 4 * 4 + 4 * 2;
+
+/// Dictionary laid out for querying in place: a header, band ranges, fixed-width records, a
+/// string table, relation ids and the record ids sorted by spelling
+pub struct DictView<'a> {
+    bytes: &'a [u8],
+    len: usize,
+    bands: usize,
+    sections: [usize; 5],
+}
+
+fn u16_at(bytes: &[u8], at: usize) -> u16 {
+    u16::from_le_bytes(bytes[at..at + 2].try_into().unwrap())
+}
+
+fn u32_at(bytes: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap())
+}
+
+fn u64_at(bytes: &[u8], at: usize) -> u64 {
+    u64::from_le_bytes(bytes[at..at + 8].try_into().unwrap())
+}
+
+impl<'a> DictView<'a> {
+    const BANDS: usize = 0;
+    const RECORDS: usize = 1;
+    const STRINGS: usize = 2;
+    const RELATIONS: usize = 3;
+    const SORTED: usize = 4;
+
+    /// Lays out rows, which must be in id order, with their band definitions, failing when the
+    /// dictionary outgrows the widths of the layout
+    pub fn build(rows: &[Row], bands: &Bands) -> Result<Vec<u8>, ArtifactError> {
+        let mut strings = String::new();
+        let mut string = |x: &str| {
+            let at = strings.len() as u32;
+            strings.push_str(x);
+            (at, x.len() as u32)
+        };
+        let mut records = Vec::with_capacity(rows.len() * RECORD_LEN);
+        let mut relations: Vec<u32> = Vec::new();
+        for row in rows {
+            let desc = row.desc.iter().map(|x| x.to_string()).collect::<Vec<_>>().join(";;;");
+            let exam = row.exam.join(",");
+            for (at, len) in [
+                string(&row.word), string(&row.head), string(&desc), string(&exam),
+            ] {
+                records.extend_from_slice(&at.to_le_bytes());
+                records.extend_from_slice(&len.to_le_bytes());
+            }
+            records.extend_from_slice(&row.freq.to_le_bytes());
+            records.extend_from_slice(&(row.lv as u32).to_le_bytes());
+            records.extend_from_slice(&(row.family as u32).to_le_bytes());
+            records.extend_from_slice(&(relations.len() as u32).to_le_bytes());
+            for relation in [&row.sim, &row.incl, &row.incl_rev, &row.sound] {
+                let len = u16::try_from(relation.len()).map_err(|_| ArtifactError::Malformed(
+                    format!("too many related words for {:?}", row.word)))?;
+                records.extend_from_slice(&len.to_le_bytes());
+            }
+            for relation in [&row.sim, &row.incl, &row.incl_rev, &row.sound] {
+                relations.extend(relation.iter().map(|x| *x as u32));
+            }
+        }
+        if strings.len() > u32::MAX as usize || relations.len() > u32::MAX as usize {
+            return Err(ArtifactError::Malformed("dictionary too large to map".to_string()));
+        }
+        let mut sorted = (0..rows.len() as u32).collect::<Vec<_>>();
+        sorted.sort_by(|x, y| rows[*x as usize].word.cmp(&rows[*y as usize].word));
+        let sections = [
+            bands.ranges.iter()
+                .flat_map(|x| [x.start, x.end])
+                .flat_map(|x| x.to_le_bytes())
+                .collect::<Vec<_>>(),
+            records,
+            strings.into_bytes(),
+            relations.iter().flat_map(|x| x.to_le_bytes()).collect(),
+            sorted.iter().flat_map(|x| x.to_le_bytes()).collect(),
+        ];
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&MAPPED_MAGIC);
+        bytes.extend_from_slice(&MAPPED_VERSION.to_le_bytes());
+        bytes.extend_from_slice(&0u16.to_le_bytes());
+        bytes.extend_from_slice(&(rows.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&(bands.len() as u32).to_le_bytes());
+        let mut at = HEADER_LEN;
+        for section in &sections {
+            bytes.extend_from_slice(&(at as u64).to_le_bytes());
+            at += section.len();
+        }
+        sections.iter().for_each(|x| bytes.extend_from_slice(x));
+        Ok(bytes)
+    }
+
+    /// Reads the header, checking that every offset stays inside its section so that queries
+    /// never read out of bounds
+    pub fn new(bytes: &'a [u8]) -> Result<Self, ArtifactError> {
+        let view = Self::parse(bytes)?;
+        view.validate()?;
+        Ok(view)
+    }
+
+    fn parse(bytes: &'a [u8]) -> Result<Self, ArtifactError> {
+        if bytes.len() < HEADER_LEN || !bytes.starts_with(&MAPPED_MAGIC) {
+            return Err(ArtifactError::Malformed("not a mapped dictionary".to_string()));
+        }
+        let version = u16_at(bytes, 4);
+        if version != MAPPED_VERSION {
+            return Err(ArtifactError::Version(version));
+        }
+        let len = u32_at(bytes, 8) as usize;
+        let bands = u32_at(bytes, 12) as usize;
+        let mut sections = [0; 5];
+        for (i, section) in sections.iter_mut().enumerate() {
+            *section = u64_at(bytes, 16 + i * 8) as usize;
+        }
+        let view = DictView { bytes, len, bands, sections };
+        let sorted_end = sections[Self::SORTED].checked_add(len * 4);
+        if sections[Self::BANDS] != HEADER_LEN || sections.windows(2).any(|x| x[0] > x[1])
+            || sorted_end != Some(bytes.len())
+            || sections[Self::RECORDS] - sections[Self::BANDS] != bands * 8
+            || sections[Self::STRINGS] - sections[Self::RECORDS] != len * RECORD_LEN
+            || (sections[Self::SORTED] - sections[Self::RELATIONS]) % 4 != 0 {
+            return Err(ArtifactError::Malformed("inconsistent section offsets".to_string()));
+        }
+        Ok(view)
+    }
+
+    fn validate(&self) -> Result<(), ArtifactError> {
+        let malformed = |x: &str| Err(ArtifactError::Malformed(x.to_string()));
+        let Ok(strings) = std::str::from_utf8(
+            &self.bytes[self.sections[Self::STRINGS]..self.sections[Self::RELATIONS]]) else {
+            return malformed("string table is not UTF-8");
+        };
+        let mut begin = 0;
+        for band in &self.bands().ranges {
+            if band.start < begin || band.start > band.end || band.end as usize > self.len {
+                return malformed("band out of order or past the last word");
+            }
+            begin = band.end;
+        }
+        let relations = (self.sections[Self::SORTED] - self.sections[Self::RELATIONS]) / 4;
+        let related = &self.bytes[self.sections[Self::RELATIONS]..self.sections[Self::SORTED]];
+        if related.chunks_exact(4).any(|x| u32_at(x, 0) as usize >= self.len) {
+            return malformed("related id out of range");
+        }
+        for id in 0..self.len {
+            for index in 0..4 {
+                let at = self.field(id, index * 2) as usize;
+                let len = self.field(id, index * 2 + 1) as usize;
+                if strings.get(at..at + len).is_none() {
+                    return malformed("string out of the string table");
+                }
+            }
+            let record = self.sections[Self::RECORDS] + id * RECORD_LEN;
+            let lens = (0..4).map(|i| u16_at(self.bytes, record + 48 + i * 2) as usize);
+            if self.field(id, 11) as usize + lens.sum::<usize>() > relations {
+                return malformed("relations out of the relation array");
+            }
+            if self.field(id, 9) as usize >= self.bands {
+                return malformed("level out of the bands");
+            }
+            if self.family(id) >= self.len {
+                return malformed("family out of range");
+            }
+            if self.sorted(id) >= self.len {
+                return malformed("sorted id out of range");
+            }
+        }
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn bands(&self) -> Bands {
+        let at = self.sections[Self::BANDS];
+        Bands {
+            ranges: (0..self.bands)
+                .map(|i| u32_at(self.bytes, at + i * 8)..u32_at(self.bytes, at + i * 8 + 4))
+                .collect(),
+        }
+    }
+
+    fn field(&self, id: usize, index: usize) -> u32 {
+        u32_at(self.bytes, self.sections[Self::RECORDS] + id * RECORD_LEN + index * 4)
+    }
+
+    /// Offsets were checked to fall on character boundaries by `new`
+    fn string(&self, id: usize, index: usize) -> &'a str {
+        let at = self.sections[Self::STRINGS] + self.field(id, index * 2) as usize;
+        let len = self.field(id, index * 2 + 1) as usize;
+        std::str::from_utf8(&self.bytes[at..at + len]).unwrap()
+    }
+
+    pub fn word(&self, id: usize) -> &'a str {
+        self.string(id, 0)
+    }
+
+    pub fn head(&self, id: usize) -> &'a str {
+        self.string(id, 1)
+    }
+
+    pub fn desc(&self, id: usize) -> impl Iterator<Item = Definition> + 'a {
+        self.string(id, 2).split(";;;").filter(|x| !x.is_empty()).map(Definition::parse)
+    }
+
+    pub fn exam(&self, id: usize) -> impl Iterator<Item = &'a str> {
+        self.string(id, 3).split(',').filter(|x| !x.is_empty())
+    }
+
+    pub fn freq(&self, id: usize) -> u32 {
+        self.field(id, 8)
+    }
+
+    pub fn lv(&self, id: usize) -> u8 {
+        self.field(id, 9) as u8
+    }
+
+    pub fn family(&self, id: usize) -> usize {
+        self.field(id, 10) as usize
+    }
+
+    /// Ids of the `index`th relation of a word, in the order sim, incl, incl_rev, sound
+    fn relation(&self, id: usize, index: usize) -> impl Iterator<Item = usize> + 'a {
+        let record = self.sections[Self::RECORDS] + id * RECORD_LEN;
+        let lens = (0..4).map(|i| u16_at(self.bytes, record + 48 + i * 2) as usize);
+        let skip = lens.clone().take(index).sum::<usize>();
+        let len = lens.clone().nth(index).unwrap();
+        let begin = self.sections[Self::RELATIONS] + (self.field(id, 11) as usize + skip) * 4;
+        let bytes = self.bytes;
+        (0..len).map(move |i| u32_at(bytes, begin + i * 4) as usize)
+    }
+
+    pub fn sim(&self, id: usize) -> impl Iterator<Item = usize> + 'a {
+        self.relation(id, 0)
+    }
+
+    pub fn incl(&self, id: usize) -> impl Iterator<Item = usize> + 'a {
+        self.relation(id, 1)
+    }
+
+    pub fn incl_rev(&self, id: usize) -> impl Iterator<Item = usize> + 'a {
+        self.relation(id, 2)
+    }
+
+    pub fn sound(&self, id: usize) -> impl Iterator<Item = usize> + 'a {
+        self.relation(id, 3)
+    }
+
+    fn sorted(&self, i: usize) -> usize {
+        u32_at(self.bytes, self.sections[Self::SORTED] + i * 4) as usize
+    }
+
+    /// Ids of the words spelled `word`, found by binary search
+    pub fn find(&self, word: &str) -> Range<usize> {
+        let partition = |f: &dyn Fn(&str) -> bool| {
+            let (mut low, mut high) = (0, self.len);
+            while low < high {
+                let mid = (low + high) / 2;
+                if f(self.word(self.sorted(mid))) { low = mid + 1 } else { high = mid }
+            }
+            low
+        };
+        partition(&|x| x < word)..partition(&|x| x <= word)
+    }
+
+    /// Id of the most frequent word spelled `word`
+    pub fn id(&self, word: &str) -> Option<usize> {
+        self.find(word).map(|i| self.sorted(i)).min()
+    }
+
+    pub fn row(&self, id: usize) -> Row {
+        Row {
+            id,
+            word: self.word(id).to_string(),
+            freq: self.freq(id),
+            desc: self.desc(id).collect(),
+            lv: self.lv(id),
+            sim: self.sim(id).collect(),
+            incl: self.incl(id).collect(),
+            incl_rev: self.incl_rev(id).collect(),
+            exam: self.exam(id).map(|x| x.to_string()).collect(),
+            head: self.head(id).to_string(),
+            sound: self.sound(id).collect(),
+            family: self.family(id),
+        }
+    }
+}
+
+/// A mapped dictionary file, queried through `view`
+#[cfg(feature = "mmap")]
+pub struct MappedDict {
+    map: memmap2::Mmap,
+}
+
+#[cfg(feature = "mmap")]
+impl MappedDict {
+    pub fn open(path: impl AsRef<std::path::Path>) -> std::io::Result<Self> {
+        let file = std::fs::File::open(path)?;
+        // the file is only ever replaced by generation, never written in place
+        let map = unsafe { memmap2::Mmap::map(&file)? };
+        DictView::new(&map)
+            .map_err(|x| std::io::Error::new(std::io::ErrorKind::InvalidData, x.to_string()))?;
+        Ok(MappedDict { map })
+    }
+
+    pub fn view(&self) -> DictView<'_> {
+        // checked when opened
+        DictView::parse(&self.map).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rows() -> Vec<Row> {
+        ["the", "book", "books", "bookcase"].iter().enumerate().map(|(id, word)| Row {
+            id,
+            word: word.to_string(),
+            freq: 100 - id as u32,
+            desc: vec![Definition::parse("n. 书；书籍")],
+            lv: (id / 2) as u8,
+            sim: (0..4).filter(|x| *x != id).collect(),
+            incl: if id == 1 { vec![2, 3] } else { Vec::new() },
+            incl_rev: if id > 1 { vec![1] } else { Vec::new() },
+            exam: vec!["CET4".to_string()],
+            head: "book".to_string(),
+            sound: Vec::new(),
+            family: id.min(1),
+        }).collect()
+    }
+
+    fn bands() -> Bands {
+        Bands { ranges: vec![0..2, 2..4] }
+    }
+
+    /// Bytes of the built dictionary with the `index`th field of word `id` replaced
+    fn corrupt(id: usize, index: usize, value: u32) -> Vec<u8> {
+        let mut bytes = DictView::build(&rows(), &bands()).unwrap();
+        let at = u64_at(&bytes, 16 + DictView::RECORDS * 8) as usize + id * RECORD_LEN + index * 4;
+        bytes[at..at + 4].copy_from_slice(&value.to_le_bytes());
+        bytes
+    }
+
+    #[test]
+    fn build_then_view() {
+        let bytes = DictView::build(&rows(), &bands()).unwrap();
+        let view = DictView::new(&bytes).unwrap();
+        assert_eq!(view.len(), 4);
+        assert_eq!(view.bands(), bands());
+        assert_eq!((0..4).map(|x| view.row(x)).collect::<Vec<_>>(), rows());
+        assert_eq!(view.id("books"), Some(2));
+        assert_eq!(view.id("cook"), None);
+    }
+
+    #[test]
+    fn reject_corrupted() {
+        for (id, index, value) in [(1, 0, 1000), (2, 9, 2), (3, 10, 4), (0, 11, 1000)] {
+            assert!(DictView::new(&corrupt(id, index, value)).is_err(),
+                    "field {} of word {} set to {}", index, id, value);
+        }
+        let mut bytes = DictView::build(&rows(), &bands()).unwrap();
+        bytes.truncate(bytes.len() - 1);
+        assert!(DictView::new(&bytes).is_err());
+        let mut bytes = DictView::build(&rows(), &bands()).unwrap();
+        bytes[HEADER_LEN + 4..HEADER_LEN + 8].copy_from_slice(&5u32.to_le_bytes());
+        assert!(DictView::new(&bytes).is_err());
+        let mut bytes = DictView::build(&rows(), &bands()).unwrap();
+        let at = u64_at(&bytes, 16 + DictView::RELATIONS * 8) as usize;
+        bytes[at..at + 4].copy_from_slice(&4u32.to_le_bytes());
+        assert!(DictView::new(&bytes).is_err());
+    }
+}
@@ -1,15 +1,13 @@
-use std::collections::HashMap;
 use std::env::args;
 use std::fs;
 use rayon::prelude::*;
-use vcbe_core::{Evidence, Row};
+use vcbe_core::{Bands, DictView, Evidence, MappedDict};
 
 fn main() {
-    let data: Vec<Row> = rmp_serde::from_slice(&fs::read("rows.rmp").unwrap()).unwrap();
+    let mapped = MappedDict::open("rows.vcbm").unwrap();
+    let dict = mapped.view();
+    let bands = dict.bands();
     // main_weight_density(&data);
-    let dict = data.iter()
-        .map(|r| (r.word.clone(), r.clone()))
-        .collect::<HashMap<_, _>>();
     let cases = args().nth(1).unwrap();
     let cases = fs::read_to_string(cases).unwrap();
     let cases = cases.lines()
@@ -22,7 +20,7 @@ fn main() {
     let pb = indicatif::ProgressBar::new(cases.len() as u64);
     let results = cases.par_iter()
         .map(|(k, u)| { 
-            let res = process(&dict, k, u);
+            let res = process(&dict, &bands, k, u);
             pb.inc(1);
             res
         })
@@ -33,37 +31,40 @@ fn main() {
 }
 
 fn process(
-    dict: &HashMap<String, Row>, known: &[&str], unknown: &[&str]
+    dict: &DictView, bands: &Bands, known: &[&str], unknown: &[&str]
 ) -> (usize, usize, usize) {
     let evidences: Vec<Evidence> = {
-        known.iter().filter_map(|k| {
-            let row = dict.get(*k)?;
-            Evidence {
-                id: row.id,
-                freq: row.freq,
-                lv: row.lv,
-                correct: true,
-            }.into()
-        }).chain(unknown.iter().filter_map(|k| {
-            let row = dict.get(*k)?;
-            Evidence {
-                id: row.id,
-                freq: row.freq,
-                lv: row.lv,
-                correct: false,
-            }.into()
-        })).collect()
+        known.iter().filter_map(|k| evidence(dict, k, true))
+            .chain(unknown.iter().filter_map(|k| evidence(dict, k, false)))
+            .collect()
     };
     if evidences.is_empty() {
         return (0, 0, 0);
     }
-    let uls = vcbe_core::estimate_uls(evidences.clone());
-    let rfwls = vcbe_core::estimate_rfwls(evidences.clone());
+    let uls = vcbe_core::estimate_uls(evidences.clone(), bands);
+    let rfwls = vcbe_core::estimate_rfwls(evidences.clone(), bands);
     //let mle = vcbe_core::estimate_mle(evidences.clone(), freq);
-    let heu = vcbe_core::estimate_heu(evidences.clone());
+    let heu = vcbe_core::estimate_heu(evidences.clone(), bands);
     (uls, rfwls, heu)
 }
 
+/// Parses a case item, either a plain word or `word:score` for partial credit
+fn evidence(dict: &DictView, item: &str, correct: bool) -> Option<Evidence> {
+    let (word, score) = match item.split_once(':') {
+        Some((word, score)) => (word, Some(score.parse::<f64>().ok()?)),
+        None => (item, None),
+    };
+    let id = dict.id(word)?;
+    Some(Evidence {
+        id,
+        freq: dict.freq(id),
+        lv: dict.lv(id),
+        correct,
+        score,
+        time: None,
+    })
+}
+
 // fn main_weight_density(data: &[Row]) {
 //     let one = u128::MAX / 1000_0000;
 //     let den: u128 = data.iter().map(|r| one / ((r.freq as u128).ilog2() as u128 + 1)).sum();
@@ -0,0 +1,206 @@
+use std::fs;
+use std::path::Path;
+use vcbe_core::{Definition, Entry};
+
+/// An entry with its lists joined into strings, one row of the CSV and SQLite exports, texts
+/// are joined by ";;;" and ids by ","
+#[derive(serde::Serialize, serde::Deserialize)]
+struct FlatEntry {
+    id: usize,
+    word: String,
+    head: String,
+    freq: u32,
+    list: String,
+    p_us: String,
+    p_uk: String,
+    exam: String,
+    desc: String,
+    phr: String,
+    phr_desc: String,
+    sen: String,
+    sen_desc: String,
+    lv: u8,
+    sim: String,
+    incl: String,
+    incl_rev: String,
+    sound: String,
+}
+
+const COLUMNS: [&str; 18] = [
+    "id", "word", "head", "freq", "list", "p_us", "p_uk", "exam", "desc", "phr", "phr_desc",
+    "sen", "sen_desc", "lv", "sim", "incl", "incl_rev", "sound",
+];
+
+fn join_ids(ids: &[usize]) -> String {
+    ids.iter().map(|x| x.to_string()).collect::<Vec<_>>().join(",")
+}
+
+fn split_ids(ids: &str) -> Vec<usize> {
+    ids.split(',').filter(|x| !x.is_empty()).map(|x| x.parse().unwrap()).collect()
+}
+
+/// Splits joined texts keeping the empty ones, which stay paired with their descriptions
+fn split_texts(texts: &str) -> Vec<String> {
+    if texts.is_empty() { return Vec::new(); }
+    texts.split(";;;").map(|x| x.to_string()).collect()
+}
+
+/// Splits texts and their descriptions, which have as many items, a single empty text being
+/// joined the same as none
+fn split_pairs(id: usize, texts: &str, descs: &str) -> (Vec<String>, Vec<String>) {
+    let mut texts = split_texts(texts);
+    let mut descs = split_texts(descs);
+    if texts.is_empty() && descs.len() == 1 { texts.push(String::new()); }
+    if descs.is_empty() && texts.len() == 1 { descs.push(String::new()); }
+    assert_eq!(texts.len(), descs.len(), "Unpaired texts and descriptions in entry {}.", id);
+    (texts, descs)
+}
+
+/// An entry with its id, one line of the JSONL export
+#[derive(serde::Serialize, serde::Deserialize)]
+struct IdEntry {
+    id: usize,
+    #[serde(flatten)]
+    entry: Entry,
+}
+
+impl FlatEntry {
+    fn new(id: usize, entry: &Entry) -> Self {
+        FlatEntry {
+            id,
+            word: entry.word.clone(),
+            head: entry.head.clone(),
+            freq: entry.freq,
+            list: entry.list.clone(),
+            p_us: entry.p_us.clone(),
+            p_uk: entry.p_uk.clone(),
+            exam: entry.exam.join(","),
+            desc: entry.desc.iter().map(|x| x.to_string()).collect::<Vec<_>>().join(";;;"),
+            phr: entry.phr.join(";;;"),
+            phr_desc: entry.phr_desc.join(";;;"),
+            sen: entry.sen.join(";;;"),
+            sen_desc: entry.sen_desc.join(";;;"),
+            lv: entry.lv,
+            sim: join_ids(&entry.sim),
+            incl: join_ids(&entry.incl),
+            incl_rev: join_ids(&entry.incl_rev),
+            sound: join_ids(&entry.sound),
+        }
+    }
+
+    fn entry(self) -> Entry {
+        let (phr, phr_desc) = split_pairs(self.id, &self.phr, &self.phr_desc);
+        let (sen, sen_desc) = split_pairs(self.id, &self.sen, &self.sen_desc);
+        Entry {
+            word: self.word,
+            head: self.head,
+            freq: self.freq,
+            list: self.list,
+            p_us: self.p_us,
+            p_uk: self.p_uk,
+            exam: self.exam.split(',').filter(|x| !x.is_empty()).map(|x| x.to_string()).collect(),
+            desc: split_texts(&self.desc).iter().map(|x| Definition::parse(x)).collect(),
+            phr,
+            phr_desc,
+            sen,
+            sen_desc,
+            lv: self.lv,
+            sim: split_ids(&self.sim),
+            incl: split_ids(&self.incl),
+            incl_rev: split_ids(&self.incl_rev),
+            sound: split_ids(&self.sound),
+        }
+    }
+}
+
+pub fn write_jsonl(entries: &[Entry], path: &Path) {
+    let lines = entries.iter()
+        .enumerate()
+        .map(|(id, entry)| serde_json::to_string(&IdEntry { id, entry: entry.clone() }).unwrap())
+        .collect::<Vec<_>>();
+    fs::write(path, lines.join("\n") + "\n").unwrap();
+}
+
+pub fn write_csv(entries: &[Entry], path: &Path) {
+    let mut writer = csv::Writer::from_path(path).unwrap();
+    for (i, entry) in entries.iter().enumerate() {
+        writer.serialize(FlatEntry::new(i, entry)).unwrap();
+    }
+    writer.flush().unwrap();
+}
+
+pub fn write_sqlite(entries: &[Entry], path: &Path) {
+    let _ = fs::remove_file(path);
+    let mut conn = rusqlite::Connection::open(path).unwrap();
+    let columns = COLUMNS.iter()
+        .map(|x| match *x {
+            "id" => "id integer primary key".to_string(),
+            "freq" | "lv" => format!("{} integer not null", x),
+            x => format!("{} text not null", x),
+        })
+        .collect::<Vec<_>>();
+    conn.execute(&format!("create table entries ({})", columns.join(", ")), []).unwrap();
+    let insert = format!("insert into entries ({}) values ({})",
+                         COLUMNS.join(", "), vec!["?"; COLUMNS.len()].join(", "));
+    let tx = conn.transaction().unwrap();
+    {
+        let mut stmt = tx.prepare(&insert).unwrap();
+        for (i, entry) in entries.iter().enumerate() {
+            let x = FlatEntry::new(i, entry);
+            stmt.execute(rusqlite::params![
+                x.id as i64, x.word, x.head, x.freq, x.list, x.p_us, x.p_uk, x.exam, x.desc, x.phr,
+                x.phr_desc, x.sen, x.sen_desc, x.lv, x.sim, x.incl, x.incl_rev, x.sound,
+            ]).unwrap();
+        }
+    }
+    tx.commit().unwrap();
+}
+
+/// Reads entries back from any of the exports, by file extension, in id order
+pub fn read_entries(path: &Path) -> Vec<Entry> {
+    let extension = path.extension().and_then(|x| x.to_str()).unwrap_or("");
+    let mut flat = match extension {
+        "jsonl" => {
+            let mut lines = fs::read_to_string(path).unwrap().lines()
+                .filter(|x| !x.trim().is_empty())
+                .map(|x| serde_json::from_str::<IdEntry>(x).expect("Malformed JSON line."))
+                .collect::<Vec<_>>();
+            lines.sort_by_key(|x| x.id);
+            return lines.into_iter().map(|x| x.entry).collect();
+        }
+        "csv" => csv::Reader::from_path(path).unwrap()
+            .deserialize::<FlatEntry>()
+            .map(|x| x.expect("Malformed CSV row."))
+            .collect::<Vec<_>>(),
+        "sqlite" | "db" => {
+            let conn = rusqlite::Connection::open(path).unwrap();
+            let query = format!("select {} from entries", COLUMNS.join(", "));
+            let mut stmt = conn.prepare(&query).unwrap();
+            let rows = stmt.query_map([], |row| Ok(FlatEntry {
+                id: row.get::<_, i64>(0)? as usize,
+                word: row.get(1)?,
+                head: row.get(2)?,
+                freq: row.get(3)?,
+                list: row.get(4)?,
+                p_us: row.get(5)?,
+                p_uk: row.get(6)?,
+                exam: row.get(7)?,
+                desc: row.get(8)?,
+                phr: row.get(9)?,
+                phr_desc: row.get(10)?,
+                sen: row.get(11)?,
+                sen_desc: row.get(12)?,
+                lv: row.get(13)?,
+                sim: row.get(14)?,
+                incl: row.get(15)?,
+                incl_rev: row.get(16)?,
+                sound: row.get(17)?,
+            })).unwrap();
+            rows.map(|x| x.unwrap()).collect::<Vec<_>>()
+        }
+        x => panic!("Unknown entry export format {:?}.", x),
+    };
+    // edited files may have been reordered, ids are positions in the original order
+    flat.sort_by_key(|x| x.id);
+    flat.into_iter().map(|x| x.entry()).collect()
+}
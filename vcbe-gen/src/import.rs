@@ -0,0 +1,242 @@
+use std::fs;
+use std::io::{Cursor, Read, Write};
+use std::path::Path;
+use vcbe_core::{Metadata, Word};
+use crate::desc_migrate;
+
+/// A word of a user supplied list, only `word` is mandatory
+#[derive(serde::Deserialize)]
+struct ListWord {
+    word: String,
+    #[serde(default)]
+    freq: Option<u32>,
+    #[serde(default)]
+    head: String,
+    /// Definitions separated by `|`
+    #[serde(default)]
+    desc: String,
+    #[serde(default)]
+    p_us: String,
+    #[serde(default)]
+    p_uk: String,
+    /// Exam lists separated by `,`
+    #[serde(default)]
+    exam: String,
+}
+
+/// A word of a JSON lines list, laid out like `Word` with everything but `word` optional
+#[derive(serde::Deserialize)]
+struct JsonWord {
+    word: String,
+    #[serde(default)]
+    freq: Option<u32>,
+    #[serde(default)]
+    head: String,
+    #[serde(default)]
+    list: String,
+    #[serde(default)]
+    p_us: String,
+    #[serde(default)]
+    p_uk: String,
+    #[serde(default)]
+    exam: Vec<String>,
+    #[serde(default)]
+    desc: Vec<String>,
+    #[serde(default)]
+    phr: Vec<String>,
+    #[serde(default)]
+    phr_desc: Vec<String>,
+    #[serde(default)]
+    sen: Vec<String>,
+    #[serde(default)]
+    sen_desc: Vec<String>,
+}
+
+fn new_word(word: String, head: String, desc: &[&str], meta: &Metadata) -> Word {
+    Word {
+        word,
+        head,
+        freq: 0,
+        list: meta.name.clone(),
+        p_us: String::new(),
+        p_uk: String::new(),
+        exam: Vec::new(),
+        desc: desc.iter()
+            .map(|x| x.trim())
+            .filter(|x| !x.is_empty())
+            .flat_map(|x| desc_migrate(x, meta))
+            .collect(),
+        phr: Vec::new(),
+        phr_desc: Vec::new(),
+        sen: Vec::new(),
+        sen_desc: Vec::new(),
+    }
+}
+
+/// Lists without frequencies are taken to be ordered from the most frequent word, which gets
+/// a frequency equal to the length of the list
+fn rank_frequencies(words: &mut [Word], freq: &[Option<u32>]) {
+    let total = words.len() as u32;
+    for (i, (word, freq)) in words.iter_mut().zip(freq).enumerate() {
+        word.freq = freq.unwrap_or(total - i as u32);
+    }
+}
+
+/// Reads a CSV or TSV word list with a header naming the columns, among "word", "freq",
+/// "head", "desc", "p_us", "p_uk" and "exam"
+pub fn import_csv(path: &Path, delimiter: u8, meta: &Metadata) -> Vec<Word> {
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_path(path)
+        .expect("Error reading word list.");
+    let (mut words, freq): (Vec<_>, Vec<_>) = reader.deserialize::<ListWord>()
+        .map(|x| x.expect("Malformed word list row."))
+        .map(|x| {
+            let mut word = new_word(x.word, x.head, &x.desc.split('|').collect::<Vec<_>>(), meta);
+            word.p_us = x.p_us;
+            word.p_uk = x.p_uk;
+            word.exam = x.exam.split(',')
+                .map(|x| x.trim().to_uppercase())
+                .filter(|x| !x.is_empty())
+                .collect();
+            (word, x.freq)
+        })
+        .unzip();
+    rank_frequencies(&mut words, &freq);
+    words
+}
+
+/// Reads one JSON object per line, see `JsonWord`
+pub fn import_jsonl(path: &Path, meta: &Metadata) -> Vec<Word> {
+    let text = fs::read_to_string(path).expect("Error reading word list.");
+    let (mut words, freq): (Vec<_>, Vec<_>) = text.lines()
+        .filter(|x| !x.trim().is_empty())
+        .map(|x| serde_json::from_str::<JsonWord>(x).expect("Malformed JSON line."))
+        .map(|x| {
+            let desc = x.desc.iter().map(|x| x.as_str()).collect::<Vec<_>>();
+            let mut word = new_word(x.word, x.head, &desc, meta);
+            if !x.list.is_empty() { word.list = x.list; }
+            word.p_us = x.p_us;
+            word.p_uk = x.p_uk;
+            word.exam = x.exam;
+            word.phr = x.phr;
+            word.phr_desc = x.phr_desc;
+            word.sen = x.sen;
+            word.sen_desc = x.sen_desc;
+            (word, x.freq)
+        })
+        .unzip();
+    rank_frequencies(&mut words, &freq);
+    words
+}
+
+/// Plain text of an Anki field, line breaks separating definitions
+fn strip_html(field: &str) -> String {
+    let field = field.replace("<br>", "\n").replace("<br/>", "\n").replace("<br />", "\n")
+        .replace("</div>", "\n");
+    let mut text = String::with_capacity(field.len());
+    let mut in_tag = false;
+    for c in field.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => in_tag = false,
+            c if !in_tag => text.push(c),
+            _ => {}
+        }
+    }
+    text.replace("&nbsp;", " ").replace("&lt;", "<").replace("&gt;", ">")
+        .replace("&quot;", "\"").replace("&#39;", "'").replace("&amp;", "&")
+}
+
+/// Words from Anki notes, the first field being the word and the second its definitions,
+/// notes come in deck order which is taken as frequency order
+fn anki_words(notes: Vec<Vec<String>>, meta: &Metadata) -> Vec<Word> {
+    let (mut words, freq): (Vec<_>, Vec<_>) = notes.into_iter()
+        .filter(|x| x.len() >= 2)
+        .map(|x| {
+            let front = strip_html(&x[0]).trim().to_string();
+            let back = strip_html(&x[1]);
+            (new_word(front, String::new(), &back.lines().collect::<Vec<_>>(), meta), None)
+        })
+        .filter(|x| !x.0.word.is_empty())
+        .unzip();
+    rank_frequencies(&mut words, &freq);
+    words
+}
+
+/// Reads an Anki "Notes in Plain Text" export, honoring its `#separator` header
+pub fn import_anki_text(path: &Path, meta: &Metadata) -> Vec<Word> {
+    let text = fs::read_to_string(path).expect("Error reading Anki export.");
+    let mut separator = b'\t';
+    // headers only come first, later lines starting with `#` may be inside quoted fields
+    let mut body = text.as_str();
+    while let Some(rest) = body.strip_prefix('#') {
+        let (header, rest) = rest.split_once('\n').unwrap_or((rest, ""));
+        if let Some(value) = header.trim_end().strip_prefix("separator:") {
+            separator = match value {
+                "tab" => b'\t',
+                "comma" => b',',
+                "semicolon" => b';',
+                "pipe" => b'|',
+                "space" => b' ',
+                x => x.bytes().next().unwrap_or(b'\t'),
+            };
+        }
+        body = rest;
+    }
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(separator)
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(body.as_bytes());
+    let notes = reader.records()
+        .map(|x| x.expect("Malformed Anki export."))
+        .map(|x| x.iter().map(|x| x.to_string()).collect())
+        .collect();
+    anki_words(notes, meta)
+}
+
+/// Reads an Anki package, whose notes are in an SQLite collection inside a zip archive
+pub fn import_apkg(path: &Path, meta: &Metadata) -> Vec<Word> {
+    let file = fs::File::open(path).expect("Error reading Anki package.");
+    let mut archive = zip::ZipArchive::new(file).expect("Malformed Anki package.");
+    // newer packages keep a zstd compressed collection next to a legacy placeholder
+    let mut collection = Vec::new();
+    for (name, compressed) in [
+        ("collection.anki21b", true), ("collection.anki21", false), ("collection.anki2", false),
+    ] {
+        let Ok(mut file) = archive.by_name(name) else { continue };
+        file.read_to_end(&mut collection).unwrap();
+        if compressed {
+            collection = zstd::decode_all(Cursor::new(collection)).unwrap();
+        }
+        break;
+    }
+    assert!(!collection.is_empty(), "No collection found in Anki package.");
+    // a file of its own so that concurrent imports do not overwrite each other
+    let mut temp = tempfile::NamedTempFile::new().unwrap();
+    temp.write_all(&collection).unwrap();
+    temp.flush().unwrap();
+    let conn = rusqlite::Connection::open(temp.path()).unwrap();
+    let notes = conn.prepare("SELECT flds FROM notes ORDER BY id").unwrap()
+        .query_map([], |row| row.get::<_, String>(0)).unwrap()
+        .map(|x| x.unwrap().split('\x1f').map(|x| x.to_string()).collect())
+        .collect();
+    drop(conn);
+    anki_words(notes, meta)
+}
+
+/// Imports a word list, the format coming from `VCBE_IMPORT_FORMAT` or the file extension
+pub fn import(path: &Path, format: Option<&str>, meta: &Metadata) -> Vec<Word> {
+    let extension = path.extension().and_then(|x| x.to_str()).unwrap_or("");
+    match format.unwrap_or(extension) {
+        "csv" => import_csv(path, b',', meta),
+        "tsv" => import_csv(path, b'\t', meta),
+        "jsonl" => import_jsonl(path, meta),
+        "txt" | "anki" => import_anki_text(path, meta),
+        "apkg" => import_apkg(path, meta),
+        x => panic!("Unknown import format {:?}.", x),
+    }
+}
@@ -0,0 +1,147 @@
+use std::collections::HashMap;
+use std::fmt;
+use vcbe_core::{Bands, Row};
+
+/// A problem found in the generated rows, `fatal` ones break the server
+#[derive(serde::Serialize)]
+pub struct Issue {
+    pub kind: &'static str,
+    pub id: usize,
+    pub word: String,
+    pub detail: String,
+    pub fatal: bool,
+}
+
+#[derive(serde::Serialize)]
+pub struct LevelStats {
+    pub lv: u8,
+    pub words: usize,
+    pub freq_min: u32,
+    pub freq_max: u32,
+    pub freq_total: u64,
+    pub definitions: usize,
+    pub sim: usize,
+    pub incl: usize,
+    pub sound: usize,
+    pub families: usize,
+}
+
+#[derive(serde::Serialize)]
+pub struct Report {
+    pub words: usize,
+    pub issues: Vec<Issue>,
+    pub counts: HashMap<&'static str, usize>,
+    pub levels: Vec<LevelStats>,
+}
+
+impl Report {
+    pub fn fatal(&self) -> usize {
+        self.issues.iter().filter(|x| x.fatal).count()
+    }
+}
+
+pub fn validate(rows: &[Row], bands: &Bands) -> Report {
+    let mut issues = Vec::new();
+    let mut issue = |kind, row: &Row, detail: String, fatal| issues.push(Issue {
+        kind,
+        id: row.id,
+        word: row.word.clone(),
+        detail,
+        fatal,
+    });
+    let mut seen = HashMap::new();
+    for (i, row) in rows.iter().enumerate() {
+        if row.id != i {
+            issue("misplaced id", row, format!("found at {}", i), true);
+        }
+        if row.desc.is_empty() {
+            issue("empty desc", row, String::new(), false);
+        }
+        if row.freq == 0 {
+            issue("zero frequency", row, String::new(), false);
+        }
+        if let Some(first) = seen.insert(row.word.as_str(), row.id) {
+            issue("duplicate word", row, format!("same as {}", first), false);
+        }
+        if bands.level_of(row.id as u32) != Some(row.lv) {
+            issue("wrong level", row, format!("level {} outside its band", row.lv), true);
+        }
+        if row.family >= rows.len() {
+            issue("dangling id", row, format!("family {}", row.family), true);
+        }
+        for (relation, ids) in [
+            ("sim", &row.sim), ("incl", &row.incl), ("incl_rev", &row.incl_rev),
+            ("sound", &row.sound),
+        ] {
+            for id in ids.iter().filter(|x| **x >= rows.len()) {
+                issue("dangling id", row, format!("{} {}", relation, id), true);
+            }
+        }
+        // every inclusion has its reverse link and the other way round
+        for (relation, reverse, ids) in [
+            ("incl", "incl_rev", &row.incl), ("incl_rev", "incl", &row.incl_rev),
+        ] {
+            for &id in ids.iter().filter(|x| **x < rows.len()) {
+                let other = &rows[id];
+                let back = if relation == "incl" { &other.incl_rev } else { &other.incl };
+                if !back.contains(&row.id) {
+                    issue("asymmetric inclusion", row,
+                          format!("{} {} lacks {} {}", relation, id, reverse, row.id), true);
+                }
+            }
+        }
+    }
+    let levels = (0..bands.len())
+        .map(|lv| {
+            let level = rows.iter().filter(|x| x.lv as usize == lv).collect::<Vec<_>>();
+            LevelStats {
+                lv: lv as u8,
+                words: level.len(),
+                freq_min: level.iter().map(|x| x.freq).min().unwrap_or(0),
+                freq_max: level.iter().map(|x| x.freq).max().unwrap_or(0),
+                freq_total: level.iter().map(|x| x.freq as u64).sum(),
+                definitions: level.iter().map(|x| x.desc.len()).sum(),
+                sim: level.iter().map(|x| x.sim.len()).sum(),
+                incl: level.iter().map(|x| x.incl.len()).sum(),
+                sound: level.iter().map(|x| x.sound.len()).sum(),
+                families: level.iter().filter(|x| x.family == x.id).count(),
+            }
+        })
+        .collect();
+    let counts = issues.iter().fold(HashMap::new(), |mut acc, x| {
+        *acc.entry(x.kind).or_insert(0) += 1;
+        acc
+    });
+    Report { words: rows.len(), issues, counts, levels }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{} words, {} issues, {} of them fatal.",
+                 self.words, self.issues.len(), self.fatal())?;
+        let mut counts = self.counts.iter().collect::<Vec<_>>();
+        counts.sort();
+        for (kind, count) in counts {
+            writeln!(f, "  {}: {}", kind, count)?;
+        }
+        writeln!(f)?;
+        writeln!(f, "{:>3} {:>6} {:>10} {:>10} {:>12} {:>6} {:>6} {:>6} {:>6} {:>8}",
+                 "lv", "words", "freq min", "freq max", "freq total", "desc", "sim", "incl",
+                 "sound", "families")?;
+        for x in &self.levels {
+            writeln!(f, "{:>3} {:>6} {:>10} {:>10} {:>12} {:>6.2} {:>6.2} {:>6.2} {:>6.2} {:>8}",
+                     x.lv, x.words, x.freq_min, x.freq_max, x.freq_total,
+                     x.definitions as f64 / x.words.max(1) as f64,
+                     x.sim as f64 / x.words.max(1) as f64,
+                     x.incl as f64 / x.words.max(1) as f64,
+                     x.sound as f64 / x.words.max(1) as f64,
+                     x.families)?;
+        }
+        writeln!(f)?;
+        for x in &self.issues {
+            writeln!(f, "{}{} #{} {:?} {}", if x.fatal { "! " } else { "  " },
+                     x.kind, x.id, x.word, x.detail)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,254 @@
+use std::collections::HashMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+use rand::distributions::Alphanumeric;
+use rand::{Rng, thread_rng};
+use rocket::serde::json::Json;
+use rocket_db_pools::sqlx;
+use rocket_db_pools::sqlx::Row;
+use vcbe_core::Message;
+use crate::{BaseConn, common, progress, WithConn};
+use crate::common::{error, Tables};
+
+const CLASSES_CREATION: [&str; 3] = [r#"
+create table if not exists classes (
+    id integer not null auto_increment primary key,
+    name varchar(128) not null,
+    dictionary varchar(64) not null,
+    invite varchar(16) not null unique,
+    token varchar(32) not null
+);
+"#, r#"
+create table if not exists class_members (
+    class_id integer not null,
+    learner varchar(64) not null,
+    role varchar(16) not null,
+    primary key (class_id, learner)
+);
+"#, r#"
+create table if not exists assignments (
+    id integer not null auto_increment primary key,
+    class_id integer not null,
+    kind varchar(16) not null,
+    assigned datetime not null,
+    due datetime not null,
+    index (class_id)
+);
+"#];
+
+static CLASSES: Tables = Tables::new(&CLASSES_CREATION);
+
+/// Length of the codes students join a class with
+const INVITE_LEN: usize = 8;
+
+/// Length of the secret tokens a teacher manages their class with
+const TOKEN_LEN: usize = 32;
+
+/// Session kinds whose results are recorded, and so can be assigned
+pub const ASSIGNABLE: [&str; 2] = ["standard", "recall"];
+
+/// Values reported for each student by `report`
+const REPORT_KEYS: [&str; 7] =
+    ["finished", "uls", "rfwls", "heu", "levels_asked", "levels_correct", "levels_known"];
+
+const TEACHER: &str = "teacher";
+const STUDENT: &str = "student";
+
+fn class_id(details: &HashMap<String, String>) -> Option<u32> {
+    details.get("class").and_then(|x| x.parse().ok())
+}
+
+/// Whether a learner teaches a class, proven by the token issued when it was created as the
+/// learner is only asserted by the client
+async fn teacher(
+    class: u32, learner: &str, details: &HashMap<String, String>, db: BaseConn
+) -> WithConn<bool> {
+    let (role, mut db) = role(class, learner, db).await;
+    if role.as_deref() != Some(TEACHER) {
+        return (false, db);
+    }
+    let token = details.get("token").map(|x| x.as_str()).unwrap_or_default();
+    let row = sqlx::query("SELECT token FROM classes WHERE id = ?")
+        .bind(class)
+        .fetch_optional(&mut **db).await.unwrap();
+    (row.is_some_and(|row| row.get::<String, _>(0) == token), db)
+}
+
+/// Role of a learner in a class, if they are a member
+async fn role(class: u32, learner: &str, mut db: BaseConn) -> WithConn<Option<String>> {
+    let role = sqlx::query("SELECT role FROM class_members WHERE class_id = ? AND learner = ?")
+        .bind(class).bind(learner)
+        .fetch_optional(&mut **db).await.unwrap()
+        .map(|row| row.get(0));
+    (role, db)
+}
+
+/// Creates a class on a dictionary taught by the learner, with the code students join it with
+/// and the token the teacher assigns and reads reports with
+pub async fn create(
+    learner: &str, details: &HashMap<String, String>, db: BaseConn
+) -> WithConn<Json<Message>> {
+    let db = CLASSES.ensure(db).await;
+    let Some(name) = details.get("name").filter(|x| !x.is_empty() && x.len() <= 128) else {
+        return (error("invalid name"), db);
+    };
+    let (dict, mut db) = common::load_dictionary(
+        details.get("dictionary").map(|x| x.as_str()), db).await;
+    let Some(dict) = dict else { return (error("invalid dictionary"), db) };
+    let invite = loop {
+        let invite = thread_rng().sample_iter(Alphanumeric)
+            .take(INVITE_LEN)
+            .map(|x| (x as char).to_ascii_uppercase())
+            .collect::<String>();
+        let taken = sqlx::query("SELECT id FROM classes WHERE invite = ?")
+            .bind(&invite)
+            .fetch_optional(&mut **db).await.unwrap();
+        if taken.is_none() { break invite; }
+    };
+    let token = thread_rng().sample_iter(Alphanumeric)
+        .take(TOKEN_LEN)
+        .map(char::from)
+        .collect::<String>();
+    let class = sqlx::query("insert into classes (name, dictionary, invite, token)
+                             values (?, ?, ?, ?)")
+        .bind(name).bind(&dict.meta.name).bind(&invite).bind(&token)
+        .execute(&mut **db).await.unwrap()
+        .last_insert_id();
+    sqlx::query("insert into class_members (class_id, learner, role) values (?, ?, ?)")
+        .bind(class).bind(learner).bind(TEACHER)
+        .execute(&mut **db).await.unwrap();
+    let details = HashMap::from([
+        ("class".to_string(), class.to_string()),
+        ("invite".to_string(), invite),
+        ("token".to_string(), token),
+        ("dictionary".to_string(), dict.meta.name),
+    ]);
+    (Json(Message { session: 0, details }), db)
+}
+
+/// Adds the learner to the class of an invite code as a student
+pub async fn join(
+    learner: &str, details: &HashMap<String, String>, db: BaseConn
+) -> WithConn<Json<Message>> {
+    let mut db = CLASSES.ensure(db).await;
+    let invite = details.get("invite").map(|x| x.trim().to_uppercase()).unwrap_or_default();
+    let row = sqlx::query("SELECT id, name, dictionary FROM classes WHERE invite = ?")
+        .bind(&invite)
+        .fetch_optional(&mut **db).await.unwrap();
+    let Some(row) = row else { return (error("invalid invite"), db) };
+    let class = row.get::<i32, _>(0) as u32;
+    // a teacher joining their own class keeps their role
+    sqlx::query("insert ignore into class_members (class_id, learner, role) values (?, ?, ?)")
+        .bind(class).bind(learner).bind(STUDENT)
+        .execute(&mut **db).await.unwrap();
+    let (role, db) = role(class, learner, db).await;
+    let details = HashMap::from([
+        ("class".to_string(), class.to_string()),
+        ("name".to_string(), row.get(1)),
+        ("dictionary".to_string(), row.get(2)),
+        ("role".to_string(), role.unwrap_or_default()),
+    ]);
+    (Json(Message { session: 0, details }), db)
+}
+
+/// Assigns a session kind to the class until a deadline given in seconds since the epoch,
+/// only the teacher can assign, with the token of the class
+pub async fn assign(
+    learner: &str, details: &HashMap<String, String>, db: BaseConn
+) -> WithConn<Json<Message>> {
+    let db = CLASSES.ensure(db).await;
+    let Some(class) = class_id(details) else { return (error("invalid class"), db) };
+    let (teacher, mut db) = teacher(class, learner, details, db).await;
+    if !teacher {
+        return (error("not the teacher of the class"), db);
+    }
+    let Some(kind) = details.get("kind").filter(|x| ASSIGNABLE.contains(&x.as_str())) else {
+        return (error("invalid session kind"), db);
+    };
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+    let Some(due) = details.get("due").and_then(|x| x.parse::<u64>().ok())
+        .filter(|x| *x > now) else {
+        return (error("invalid due"), db);
+    };
+    let assignment = sqlx::query("insert into assignments (class_id, kind, assigned, due)
+                                  values (?, ?, NOW(), FROM_UNIXTIME(?))")
+        .bind(class).bind(kind).bind(due)
+        .execute(&mut **db).await.unwrap()
+        .last_insert_id();
+    let details = HashMap::from([
+        ("assignment".to_string(), assignment.to_string()),
+    ]);
+    (Json(Message { session: 0, details }), db)
+}
+
+/// Assignments of a class, earliest deadline first, with whether the learner has finished a
+/// session of the kind since it was assigned and before it was due
+pub async fn assignments(
+    learner: &str, details: &HashMap<String, String>, db: BaseConn
+) -> WithConn<Json<Message>> {
+    let db = CLASSES.ensure(db).await;
+    let Some(class) = class_id(details) else { return (error("invalid class"), db) };
+    let (role, db) = role(class, learner, db).await;
+    if role.is_none() {
+        return (error("not a member of the class"), db);
+    }
+    let mut db = progress::RESULTS.ensure(db).await;
+    let rows = sqlx::query("SELECT a.id, a.kind, CAST(UNIX_TIMESTAMP(a.due) AS SIGNED),
+                            EXISTS(SELECT 1 FROM results r WHERE r.learner = ?
+                                   AND r.dictionary = c.dictionary AND r.kind = a.kind
+                                   AND r.finished BETWEEN a.assigned AND a.due)
+                            FROM assignments a JOIN classes c ON c.id = a.class_id
+                            WHERE a.class_id = ? ORDER BY a.due, a.id")
+        .bind(learner).bind(class)
+        .fetch_all(&mut **db).await.unwrap();
+    let column = |f: &dyn Fn(&sqlx::mysql::MySqlRow) -> String| rows.iter()
+        .map(f)
+        .collect::<Vec<_>>()
+        .join(",");
+    let details = HashMap::from([
+        ("assignments".to_string(), column(&|row| row.get::<i32, _>(0).to_string())),
+        ("kinds".to_string(), column(&|row| row.get(1))),
+        ("due".to_string(), column(&|row| row.get::<i64, _>(2).to_string())),
+        ("done".to_string(), column(&|row| (row.get::<i64, _>(3) != 0).to_string())),
+    ]);
+    (Json(Message { session: 0, details }), db)
+}
+
+/// Latest result of each student of the class on its dictionary, only for the teacher with the
+/// token of the class: values of the students are separated by ";;;" in the order of
+/// "students", levels by ","
+pub async fn report(
+    learner: &str, details: &HashMap<String, String>, db: BaseConn
+) -> WithConn<Json<Message>> {
+    let db = CLASSES.ensure(db).await;
+    let Some(class) = class_id(details) else { return (error("invalid class"), db) };
+    let (teacher, db) = teacher(class, learner, details, db).await;
+    if !teacher {
+        return (error("not the teacher of the class"), db);
+    }
+    let mut db = progress::RESULTS.ensure(db).await;
+    let students: Vec<String> = sqlx::query("SELECT learner FROM class_members
+                                             WHERE class_id = ? AND role = ? ORDER BY learner")
+        .bind(class).bind(STUDENT)
+        .fetch_all(&mut **db).await.unwrap()
+        .iter()
+        .map(|row| row.get(0))
+        .collect();
+    let dictionary: String = sqlx::query("SELECT dictionary FROM classes WHERE id = ?")
+        .bind(class)
+        .fetch_one(&mut **db).await.unwrap()
+        .get(0);
+    let mut columns = REPORT_KEYS.map(|_| Vec::with_capacity(students.len()));
+    for student in &students {
+        let (latest, next) = progress::latest(student, &dictionary, db).await;
+        db = next;
+        for (key, column) in REPORT_KEYS.iter().zip(columns.iter_mut()) {
+            // students without a result yet are left blank
+            column.push(latest.as_ref().and_then(|x| x.get(*key)).cloned().unwrap_or_default());
+        }
+    }
+    let mut details = REPORT_KEYS.iter().zip(columns)
+        .map(|(key, column)| (key.to_string(), column.join(";;;")))
+        .collect::<HashMap<_, _>>();
+    details.insert("students".to_string(), students.join(";;;"));
+    (Json(Message { session: 0, details }), db)
+}
@@ -1,54 +1,571 @@
-use rand::{Rng, thread_rng};
+use rand::{random, Rng};
+use rand::seq::SliceRandom;
+use rand_chacha::ChaCha8Rng;
+use rocket::serde::json::Json;
+use rocket::tokio::sync::{OnceCell, RwLock};
 use rocket_db_pools::{Connection, sqlx};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Instant;
+use once_cell::sync::Lazy;
 use rocket_db_pools::sqlx::Row;
-use vcbe_core::{Evidence, LV_RANGES};
+use log::warn;
+use vcbe_core::{Bands, DEFAULT_DICTIONARY, dictionary_table, Evidence, FAST_THRESHOLD, Message,
+                Metadata};
 use crate::{Base, WithConn};
 
-pub fn choose_word(history: &[(u32, bool)], lv: usize) -> u32 {
-    let mut current_word = thread_rng().gen_range(LV_RANGES[lv].clone());
-    while history.iter().any(|(x, _)| *x == current_word) {
-        current_word = thread_rng().gen_range(LV_RANGES[lv].clone());
+/// Response carrying only an error message
+pub fn error(message: &str) -> Json<Message> {
+    Json(Message {
+        session: 0,
+        details: HashMap::from([
+            ("error".to_string(), message.to_string()),
+        ]),
+    })
+}
+
+/// Tables created on first use, once per run of the server
+pub struct Tables {
+    creations: &'static [&'static str],
+    created: OnceCell<()>,
+}
+
+impl Tables {
+    pub const fn new(creations: &'static [&'static str]) -> Self {
+        Tables { creations, created: OnceCell::const_new() }
+    }
+
+    pub async fn ensure(&self, mut db: Connection<Base>) -> Connection<Base> {
+        self.created.get_or_init(|| async {
+            for creation in self.creations {
+                sqlx::query(creation).execute(&mut **db).await.unwrap();
+            }
+        }).await;
+        db
     }
-    current_word
 }
 
-pub fn choose_words(history: &[(u32, bool)], lv: usize) -> Vec<u32> {
-    let mut current_words = Vec::new();
-    while current_words.len() < 100 {
-        let mut new_word = thread_rng().gen_range(LV_RANGES[lv].clone());
-        let mut history_contains = history.iter().any(|(x, _)| *x == new_word);
-        let mut current_words_contains = current_words.contains(&new_word);
-        while history_contains || current_words_contains {
-            new_word = thread_rng().gen_range(LV_RANGES[lv].clone());
-            history_contains = history.iter().any(|(x, _)| *x == new_word);
-            current_words_contains = current_words.contains(&new_word);
+/// A single answered question, `score` is the credit given in [0, 1] and `latency` is the
+/// time taken to answer in milliseconds
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Answer {
+    pub word: u32,
+    pub score: f64,
+    pub latency: u32,
+    /// Drawn from a targeted exam list rather than uniformly from its level
+    pub listed: bool,
+}
+
+impl Answer {
+    pub fn new(word: u32, correct: bool, asked: Instant) -> Self {
+        Answer {
+            word,
+            score: if correct { 1.0 } else { 0.0 },
+            latency: asked.elapsed().as_millis() as u32,
+            listed: false,
         }
-        current_words.push(new_word);
     }
-    current_words
 }
 
-pub async fn result(
-    history: &[(u32, bool)], mut db: Connection<Base>
-) -> WithConn<HashMap<String, String>> {
-    let mut result = HashMap::new();
+/// When the current question was asked: when `/state` first served it, or when it was
+/// generated for clients submitting without fetching the state
+#[derive(Debug, Copy, Clone)]
+pub struct Asked {
+    pub at: Instant,
+    served: bool,
+}
+
+impl Default for Asked {
+    fn default() -> Self {
+        Asked::new()
+    }
+}
+
+impl Asked {
+    pub fn new() -> Self {
+        Asked { at: Instant::now(), served: false }
+    }
+
+    /// Stamps the question as asked now unless it was already served
+    pub fn serve(&mut self) {
+        if !self.served {
+            *self = Asked { at: Instant::now(), served: true };
+        }
+    }
+}
+
+/// Reads the "discount_fast" session option, either "true" for the default threshold or
+/// a threshold in milliseconds
+pub fn fast_threshold(details: &HashMap<String, String>) -> Option<u32> {
+    match details.get("discount_fast")?.as_str() {
+        "true" => Some(FAST_THRESHOLD),
+        "false" => None,
+        threshold => threshold.parse().ok(),
+    }
+}
+
+/// Random number generator owned by a session, seeded so that the same answers to a session
+/// started with the same seed get the same questions, a fixed algorithm as the one behind
+/// `StdRng` may change between versions of rand
+pub type SessionRng = ChaCha8Rng;
+
+/// Reads the "seed" session option, picks a seed at random when it is not given
+pub fn seed(details: &HashMap<String, String>) -> Result<u64, String> {
+    match details.get("seed") {
+        None => Ok(random()),
+        Some(seed) => seed.parse().map_err(|_| "invalid seed".to_string()),
+    }
+}
+
+/// Reads the "family_size" session option, whether a word family size is reported
+pub fn family_size(details: &HashMap<String, String>) -> bool {
+    details.get("family_size").is_some_and(|x| x == "true")
+}
+
+/// Parses a self-reported recall answer, "unsure" gets half of the credit
+pub fn recall_score(recall: &str) -> Option<f64> {
+    match recall {
+        "true" => Some(1.0),
+        "false" => Some(0.0),
+        "unsure" => Some(0.5),
+        _ => None,
+    }
+}
+
+/// A dictionary served side by side with others, backed by its own set of tables
+#[derive(Debug, Clone)]
+pub struct Dictionary {
+    pub meta: Metadata,
+    pub words: String,
+    pub pseudo: String,
+    pub bands: Bands,
+    /// Later columns the words table was generated without
+    pub missing: Vec<&'static str>,
+}
+
+/// Columns added to the words tables after the first databases were generated, with what is
+/// read in their place from tables without them: no exam lists, each word its own headword
+/// and family and no sound-alikes
+const LATER_COLUMNS: [(&str, &str); 4] =
+    [("exam", "''"), ("head", "word"), ("sound", "''"), ("family", "id")];
+
+impl Dictionary {
+    /// A column of the words table to query, or what stands in for it in older tables
+    pub fn column(&self, name: &'static str) -> &'static str {
+        match LATER_COLUMNS.iter().find(|x| x.0 == name) {
+            Some((_, fallback)) if self.missing.contains(&name) => fallback,
+            _ => name,
+        }
+    }
+}
+
+/// Lists the names of the dictionaries registered in the database
+pub async fn dictionaries(mut db: Connection<Base>) -> WithConn<Vec<String>> {
+    let names = match sqlx::query("SELECT name FROM dictionaries ORDER BY name")
+        .fetch_all(&mut **db).await {
+        Ok(rows) => rows.iter().map(|row| row.get(0)).collect(),
+        // databases generated before dictionaries were registered only hold the default one
+        Err(_) => vec![DEFAULT_DICTIONARY.to_string()],
+    };
+    (names, db)
+}
+
+/// Loads a registered dictionary with its metadata and band definitions, falling back to the
+/// built-in ones for databases generated before they were stored
+pub async fn load_dictionary(
+    name: Option<&str>, db: Connection<Base>
+) -> WithConn<Option<Dictionary>> {
+    let name = name.unwrap_or(DEFAULT_DICTIONARY);
+    let (names, mut db) = dictionaries(db).await;
+    // table names are built from the dictionary name, so only accept registered ones
+    if !names.iter().any(|x| x == name) {
+        return (None, db);
+    }
+    let rows = sqlx::query(&format!(
+        "SELECT begin_id, end_id FROM {} ORDER BY lv", dictionary_table("bands", name)))
+        .fetch_all(&mut **db).await;
+    let bands = match rows {
+        Ok(rows) if !rows.is_empty() => Bands {
+            ranges: rows.iter()
+                .map(|row| row.get::<i32, _>(0) as u32..row.get::<i32, _>(1) as u32)
+                .collect(),
+        },
+        _ => {
+            warn!("No band definitions found for {}, using the built-in ones.", name);
+            Bands::default()
+        }
+    };
+    let meta = sqlx::query("SELECT source, gloss FROM dictionaries WHERE name = ?")
+        .bind(name)
+        .fetch_one(&mut **db).await
+        .map(|row| Metadata { name: name.to_string(), source: row.get(0), gloss: row.get(1) })
+        .unwrap_or(Metadata { name: name.to_string(), ..Metadata::default() });
+    let words = dictionary_table("words", name);
+    let columns = sqlx::query("SELECT column_name FROM information_schema.columns
+                               WHERE table_schema = DATABASE() AND table_name = ?")
+        .bind(&words)
+        .fetch_all(&mut **db).await
+        .map(|rows| rows.iter()
+            .filter_map(|row| row.try_get::<String, _>(0).ok())
+            .collect::<Vec<_>>())
+        .unwrap_or_default();
+    let missing = LATER_COLUMNS.iter()
+        .map(|x| x.0)
+        .filter(|x| !columns.is_empty() && !columns.iter().any(|y| y.eq_ignore_ascii_case(x)))
+        .collect();
+    let dict = Dictionary {
+        meta,
+        words,
+        pseudo: dictionary_table("pseudo", name),
+        bands,
+        missing,
+    };
+    (Some(dict), db)
+}
+
+/// Number of questions needed before a result is available, three for each level
+pub fn warmup(dict: &Dictionary) -> usize {
+    dict.bands.len() * 3
+}
+
+/// Words of an exam list targeted by a session
+#[derive(Debug, Clone)]
+pub struct ExamList {
+    pub name: String,
+    pub words: Vec<u32>,
+    /// Only ask listed words instead of favoring them
+    pub restrict: bool,
+}
+
+/// Chance of asking a listed word when an exam list is favored
+pub const EXAM_WEIGHT: f64 = 0.5;
+
+/// Reads the "exam" and "exam_mode" session options and loads the listed words, gives an
+/// error message for lists without any word
+pub async fn load_exam(
+    details: &HashMap<String, String>, dict: &Dictionary, mut db: Connection<Base>
+) -> WithConn<Result<Option<ExamList>, String>> {
+    let Some(name) = details.get("exam") else { return (Ok(None), db) };
+    let name = name.to_uppercase();
+    let restrict = match details.get("exam_mode").map(|x| x.as_str()) {
+        None | Some("weight") => false,
+        Some("restrict") => true,
+        Some(_) => return (Err("invalid exam mode".to_string()), db),
+    };
+    let rows = sqlx::query(&format!(
+        "SELECT id FROM {} WHERE FIND_IN_SET(?, {})", dict.words, dict.column("exam")))
+        .bind(&name)
+        .fetch_all(&mut **db).await;
+    let Ok(rows) = rows else { return (Err("invalid exam".to_string()), db) };
+    let mut words = rows.iter()
+        .map(|row| row.get::<i32, _>(0) as u32)
+        .collect::<Vec<_>>();
+    if words.is_empty() {
+        return (Err("invalid exam".to_string()), db);
+    }
+    words.sort_unstable();
+    (Ok(Some(ExamList { name, words, restrict })), db)
+}
+
+/// Families of the asked words, databases generated before families were collected make
+/// each word its own family
+async fn asked_families(
+    history: &[Answer], dict: &Dictionary, mut db: Connection<Base>
+) -> WithConn<HashSet<u32>> {
+    let mut families = history.iter().map(|x| x.word).collect::<HashSet<_>>();
+    if history.is_empty() { return (families, db); }
+    let ids = history.iter().map(|x| x.word.to_string()).collect::<Vec<_>>().join(",");
+    let rows = sqlx::query(&format!(
+        "SELECT DISTINCT family FROM {} WHERE id IN ({})", dict.words, ids))
+        .fetch_all(&mut **db).await;
+    if let Ok(rows) = rows {
+        families = rows.iter().map(|row| row.get::<i32, _>(0) as u32).collect();
+    }
+    (families, db)
+}
+
+/// Ids of the words of a level with their families
+type LevelWords = Arc<Vec<(u32, u32)>>;
+
+/// Words of each level with their families, keyed by the words table and the level
+static LEVELS: Lazy<RwLock<HashMap<(String, usize), LevelWords>>> =
+    Lazy::new(|| RwLock::new(HashMap::new()));
+
+/// Words of a level with their families, read once for all sessions, databases generated
+/// before families were collected make each word its own family
+async fn level_families(
+    dict: &Dictionary, lv: usize, mut db: Connection<Base>
+) -> WithConn<LevelWords> {
+    let key = (dict.words.clone(), lv);
+    if let Some(words) = LEVELS.read().await.get(&key) {
+        return (words.clone(), db);
+    }
+    let range = dict.bands.ranges[lv].clone();
+    let rows = sqlx::query(&format!(
+        "SELECT id, family FROM {} WHERE id >= ? AND id < ? ORDER BY id", dict.words))
+        .bind(range.start).bind(range.end)
+        .fetch_all(&mut **db).await;
+    let words: LevelWords = Arc::new(match rows {
+        Ok(rows) => rows.iter()
+            .map(|row| (row.get::<i32, _>(0) as u32, row.get::<i32, _>(1) as u32))
+            .collect(),
+        Err(_) => range.map(|x| (x, x)).collect(),
+    });
+    LEVELS.write().await.insert(key, words.clone());
+    (words, db)
+}
+
+/// Unasked words of a level with their families, the ones outside the families already
+/// tested first
+async fn candidates(
+    history: &[Answer], dict: &Dictionary, lv: usize, db: Connection<Base>
+) -> WithConn<(Vec<(u32, u32)>, Vec<(u32, u32)>)> {
+    let (families, db) = asked_families(history, dict, db).await;
+    let (words, db) = level_families(dict, lv, db).await;
+    let asked = history.iter().map(|x| x.word).collect::<HashSet<_>>();
+    let split = words.iter()
+        .copied()
+        .filter(|(word, _)| !asked.contains(word))
+        .partition(|(_, family)| !families.contains(family));
+    (split, db)
+}
+
+/// Picks an unasked word of the level outside the families already tested, from the exam
+/// list if it is targeted and still has such words in the level, telling whether it was
+/// drawn from the list. Once the level runs out of new families they may repeat, and once it
+/// runs out of unasked words so may the words
+pub async fn choose_word(
+    history: &[Answer], dict: &Dictionary, exam: Option<&ExamList>, lv: usize,
+    rng: &mut SessionRng, db: Connection<Base>
+) -> WithConn<(u32, bool)> {
+    let ((fresh, repeated), db) = candidates(history, dict, lv, db).await;
+    if let Some(exam) = exam {
+        if exam.restrict || rng.gen_bool(EXAM_WEIGHT) {
+            let listed = fresh.iter()
+                .filter(|(word, _)| exam.words.binary_search(word).is_ok())
+                .collect::<Vec<_>>();
+            if let Some((word, _)) = listed.choose(rng) {
+                return ((*word, true), db);
+            }
+        }
+    }
+    let word = fresh.choose(rng)
+        .or_else(|| repeated.choose(rng))
+        .map(|x| x.0)
+        .unwrap_or_else(|| rng.gen_range(dict.bands.ranges[lv].clone()));
+    ((word, false), db)
+}
+
+/// Picks 100 unasked words of the level, one of each family outside the families already
+/// tested while the level has enough of them, then other unasked words, fewer once the level
+/// runs out of them
+pub async fn choose_words(
+    history: &[Answer], dict: &Dictionary, lv: usize, rng: &mut SessionRng, db: Connection<Base>
+) -> WithConn<Vec<u32>> {
+    let ((mut fresh, mut repeated), db) = candidates(history, dict, lv, db).await;
+    fresh.shuffle(rng);
+    repeated.shuffle(rng);
+    let mut families = HashSet::new();
+    let (mut current_words, others): (Vec<_>, Vec<_>) = fresh.into_iter()
+        .partition(|(_, family)| families.insert(*family));
+    current_words.truncate(100);
+    let rest = 100 - current_words.len();
+    current_words.extend(others.into_iter().chain(repeated).take(rest));
+    (current_words.into_iter().map(|x| x.0).collect(), db)
+}
+
+/// Number of word families in each level, counted at their most frequent member
+pub async fn family_counts(
+    dict: &Dictionary, mut db: Connection<Base>
+) -> WithConn<Option<Vec<usize>>> {
+    let rows = sqlx::query(&format!(
+        "SELECT lv, COUNT(*) FROM {} WHERE family = id GROUP BY lv", dict.words))
+        .fetch_all(&mut **db).await;
+    let counts = rows.ok().map(|rows| rows.iter()
+        .fold(vec![0; dict.bands.len()], |mut acc, row| {
+            let lv = row.get::<i32, _>(0) as usize;
+            if lv < acc.len() { acc[lv] = row.get::<i64, _>(1) as usize; }
+            acc
+        }));
+    (counts, db)
+}
+
+/// Ratio of pseudo-words mixed into self-reported questions
+pub const PSEUDO_RATE: f64 = 0.1;
+
+pub async fn choose_pseudo(
+    history: &[Answer], dict: &Dictionary, count: usize, rng: &mut SessionRng,
+    mut db: Connection<Base>
+) -> WithConn<Vec<u32>> {
+    let total: i64 = sqlx::query(&format!("SELECT COUNT(*) FROM {}", dict.pseudo))
+        .fetch_one(&mut **db).await.unwrap().get(0);
+    let total = total as u32;
+    let used = history.iter()
+        .map(|x| x.word)
+        .filter(|x| *x < total)
+        .collect::<HashSet<_>>()
+        .len() as u32;
+    // fewer than asked for once the pseudo-words run out, none for dictionaries without any
+    let count = count.min((total - used) as usize);
+    let mut pseudo = Vec::with_capacity(count);
+    while pseudo.len() < count {
+        let new_word = rng.gen_range(0..total);
+        if !history.iter().any(|x| x.word == new_word) && !pseudo.contains(&new_word) {
+            pseudo.push(new_word);
+        }
+    }
+    (pseudo, db)
+}
+
+pub async fn pseudo_word(
+    id: u32, dict: &Dictionary, mut db: Connection<Base>
+) -> WithConn<String> {
+    let row = sqlx::query(&format!("SELECT word FROM {} WHERE id = ?", dict.pseudo))
+        .bind(id).fetch_one(&mut **db).await.unwrap();
+    (row.get(0), db)
+}
+
+/// Corrects every self-reported count in `result` for the pseudo-words claimed known, keeping
+/// the uncorrected values under the same keys suffixed with "_raw"
+pub fn false_alarm(result: &mut HashMap<String, String>, pseudo: &[Answer], dict: &Dictionary) {
+    let rate = vcbe_core::false_alarm_rate(
+        &pseudo.iter().map(|x| x.score).collect::<Vec<_>>());
+    result.insert("false_alarm".to_string(), rate.to_string());
+    let total = |key: &str| result.get(key).and_then(|x| x.parse::<usize>().ok());
+    let (families, exam) = (total("families_total"), total("exam_total"));
+    let mut adjust = |key: &str, total: usize| {
+        let raw = result.get(key).and_then(|x| x.parse().ok())?;
+        let adjusted = vcbe_core::adjust_false_alarm(raw, total, rate);
+        result.insert(format!("{}_raw", key), raw.to_string());
+        result.insert(key.to_string(), adjusted.to_string());
+        Some(adjusted)
+    };
+    let total = dict.bands.total();
+    for key in ["uls", "rfwls", "heu"] {
+        adjust(key, total);
+    }
+    if let Some(families) = families {
+        adjust("families", families);
+    }
+    if let Some(exam) = exam.filter(|x| *x > 0) {
+        if let Some(known) = adjust("exam_known", exam) {
+            let coverage = ((known as f64 / exam as f64 * 100.0).round() as u32).to_string();
+            if let Some(raw) = result.insert("exam_coverage".to_string(), coverage) {
+                result.insert("exam_coverage_raw".to_string(), raw);
+            }
+        }
+    }
+    if let Some(raw) = result.get("levels_known").cloned() {
+        let known = raw.split(',')
+            .zip(&dict.bands.ranges)
+            .map(|(x, range)| x.parse().map(|x| {
+                vcbe_core::adjust_false_alarm(x, range.len(), rate).to_string()
+            }).unwrap_or_default())
+            .collect::<Vec<_>>()
+            .join(",");
+        result.insert("levels_known".to_string(), known);
+        result.insert("levels_known_raw".to_string(), raw);
+    }
+}
+
+/// Answers with the frequency and level of their words
+pub async fn evidences(
+    history: &[Answer], dict: &Dictionary, mut db: Connection<Base>
+) -> WithConn<Vec<Evidence>> {
     let mut evidences = Vec::with_capacity(history.len());
-    for (i, correct) in history {
-        let row = sqlx::query("SELECT freq, lv FROM words WHERE id = ?")
-            .bind(i).fetch_one(&mut **db).await.unwrap();
+    for answer in history {
+        let row = sqlx::query(&format!("SELECT freq, lv FROM {} WHERE id = ?", dict.words))
+            .bind(answer.word).fetch_one(&mut **db).await.unwrap();
         let freq: u32 = row.get::<i32, _>(0) as u32;
         let lv: u8 = row.get::<i32, _>(1) as u8;
         evidences.push(Evidence {
-            id: *i as usize,
+            id: answer.word as usize,
             freq,
             lv,
-            correct: *correct,
+            correct: answer.score >= 1.0,
+            score: Some(answer.score),
+            time: Some(answer.latency),
         });
     }
-    let est_uls = vcbe_core::estimate_uls(evidences.clone());
+    (evidences, db)
+}
+
+/// Evidences fit for the overall estimates: answers drawn from an exam list favor its words,
+/// so only the uniform draws of a level are kept where the level has any
+pub fn uniform_draws(evidences: &[Evidence], history: &[Answer]) -> Vec<Evidence> {
+    let uniform = evidences.iter().zip(history)
+        .filter(|(_, x)| !x.listed)
+        .map(|(x, _)| x.lv)
+        .collect::<HashSet<_>>();
+    evidences.iter().zip(history)
+        .filter(|(x, y)| !y.listed || !uniform.contains(&x.lv))
+        .map(|(x, _)| *x)
+        .collect()
+}
+
+pub async fn result(
+    history: &[Answer], fast: Option<u32>, dict: &Dictionary, exam: Option<&ExamList>,
+    family_size: bool, db: Connection<Base>
+) -> WithConn<HashMap<String, String>> {
+    let bands = &dict.bands;
+    let mut result = HashMap::new();
+    let (mut evidences, mut db) = evidences(history, dict, db).await;
+    result.insert("latencies".to_string(), history.iter()
+        .map(|x| x.latency.to_string())
+        .collect::<Vec<_>>()
+        .join(","));
+    if !history.is_empty() {
+        let latency = history.iter().map(|x| x.latency as u64).sum::<u64>()
+            / history.len() as u64;
+        result.insert("latency".to_string(), latency.to_string());
+    }
+    let threshold = fast.unwrap_or(FAST_THRESHOLD);
+    let fast_count = history.iter().filter(|x| x.latency < threshold).count();
+    result.insert("fast".to_string(), fast_count.to_string());
+    if let Some(threshold) = fast {
+        evidences = vcbe_core::discount_fast(evidences, threshold);
+    }
+    if let Some(exam) = exam {
+        let listed = evidences.iter()
+            .filter(|x| exam.words.binary_search(&(x.id as u32)).is_ok())
+            .copied().collect::<Vec<_>>();
+        let counts = exam.words.iter().fold(vec![0; bands.len()], |mut acc, x| {
+            if let Some(lv) = bands.level_of(*x) { acc[lv as usize] += 1; }
+            acc
+        });
+        let coverage = vcbe_core::estimate_coverage(&evidences, &listed, &counts);
+        result.insert("exam".to_string(), exam.name.clone());
+        result.insert("exam_coverage".to_string(), ((coverage * 100.0).round() as u32).to_string());
+        result.insert("exam_known".to_string(),
+                      ((coverage * exam.words.len() as f64) as usize).to_string());
+        result.insert("exam_total".to_string(), exam.words.len().to_string());
+    }
+    let answered = evidences;
+    let evidences = uniform_draws(&answered, history);
+    if family_size {
+        let (counts, next) = family_counts(dict, db).await;
+        db = next;
+        if let Some(counts) = counts {
+            let families = vcbe_core::estimate_families(&evidences, &counts);
+            result.insert("families".to_string(), families.to_string());
+            result.insert("families_total".to_string(), counts.iter().sum::<usize>().to_string());
+        }
+    }
+    // every answer counts as asked, the known words are extrapolated from the uniform draws
+    let levels = vcbe_core::level_results(&answered, bands).into_iter()
+        .zip(vcbe_core::level_results(&evidences, bands))
+        .map(|(x, y)| vcbe_core::LevelResult { known: y.known, ..x })
+        .collect::<Vec<_>>();
+    let join = |f: fn(&vcbe_core::LevelResult) -> usize| levels.iter()
+        .map(|x| f(x).to_string())
+        .collect::<Vec<_>>()
+        .join(",");
+    result.insert("levels_asked".to_string(), join(|x| x.asked));
+    result.insert("levels_correct".to_string(), join(|x| x.correct));
+    result.insert("levels_known".to_string(), join(|x| x.known));
+    let est_uls = vcbe_core::estimate_uls(evidences.clone(), bands);
     result.insert("uls".to_string(), est_uls.to_string());
-    let est_rfwls = vcbe_core::estimate_rfwls(evidences.clone());
+    let est_rfwls = vcbe_core::estimate_rfwls(evidences.clone(), bands);
     result.insert("rfwls".to_string(), est_rfwls.to_string());
     // let freq = {
     //     let rows = sqlx::query("SELECT freq FROM words")
@@ -59,7 +576,7 @@ pub async fn result(
     // };
     // let est_mle = vcbe_core::estimate_mle(evidences, freq);
     // result.insert("mle".to_string(), est_mle.to_string());
-    let est_heu = vcbe_core::estimate_heu(evidences);
+    let est_heu = vcbe_core::estimate_heu(evidences, bands);
     result.insert("heu".to_string(), est_heu.to_string());
     (result, db)
 }
@@ -2,6 +2,11 @@ mod standard;
 mod recall;
 mod common;
 mod mass_recall;
+mod study;
+mod words;
+mod recommend;
+mod progress;
+mod classroom;
 
 use std::collections::{BTreeMap, HashMap};
 use std::ops::{Deref, DerefMut};
@@ -9,7 +14,9 @@ use std::sync::Arc;
 use rocket::tokio::sync::{RwLock};
 use std::thread;
 use std::time::{Duration, Instant};
-use log::{info, warn};
+use log::info;
+#[cfg(feature = "permissive")]
+use log::warn;
 use once_cell::sync::Lazy;
 use rocket::{get, launch, options, post, Request, Response, routes};
 use rocket::http::{ContentType, Header};
@@ -43,18 +50,25 @@ async fn rocket() -> _ {
                     sessions.remove(&id);
                 }
             }
+            recommend::expire(3000).await;
         }
     });
     thread::spawn(|| {
         thread::sleep(Duration::from_secs(1));
         
     });
-    rocket::build()
+    let rocket = rocket::build()
         .attach(Base::init())
         .attach(CORS)
         .mount("/", routes![
-            index, start, start_options, state, state_options, state_post, submit, submit_options
-        ])
+            index, start, start_options, state, state_options, state_post, submit, submit_options,
+            dictionaries, search_words, word, study_due, study_grade, recommend_words,
+            learner_progress, class_create, class_join, class_assign, class_assignments,
+            class_report
+        ]);
+    #[cfg(feature = "permissive")]
+    let rocket = rocket.mount("/", routes![debug_distractors]);
+    rocket
 }
 
 pub struct CORS;
@@ -112,6 +126,18 @@ pub enum SessionInner {
     MassRecall(mass_recall::Session),
 }
 
+impl SessionInner {
+    /// Answers and dictionary of a session estimated from its answers
+    fn answers(&self) -> Option<(&[common::Answer], &common::Dictionary)> {
+        match self {
+            SessionInner::Standard(ses) => Some((&ses.history, &ses.dict)),
+            SessionInner::Recall(ses) if !ses.tyv_mode => Some((&ses.history, &ses.dict)),
+            SessionInner::Recall(_) => None,
+            SessionInner::MassRecall(ses) => Some((&ses.history, &ses.dict)),
+        }
+    }
+}
+
 impl Session {
     pub async fn create_with(inner: SessionInner) -> Arc<RwLock<Self>> {
         let id = {
@@ -154,11 +180,63 @@ pub async fn start(data: Json<Message>, db: BaseConn) -> Json<Message> {
             details: Default::default(),
         }),
         Some(kind) => {
+            let fast = common::fast_threshold(&data.details);
+            let family_size = common::family_size(&data.details);
+            let learner = match study::learner(&data.details) {
+                Ok(learner) => learner,
+                Err(error) => return Json(Message {
+                    session: 0,
+                    details: HashMap::from([
+                        ("error".to_string(), error)
+                    ])
+                }),
+            };
+            let seed = match common::seed(&data.details) {
+                Ok(seed) => seed,
+                Err(error) => return Json(Message {
+                    session: 0,
+                    details: HashMap::from([
+                        ("error".to_string(), error)
+                    ])
+                }),
+            };
+            let name = data.details.get("dictionary").map(|x| x.as_str());
+            let (dict, db) = common::load_dictionary(name, db).await;
+            let Some(dict) = dict else {
+                return Json(Message {
+                    session: 0,
+                    details: HashMap::from([
+                        ("error".to_string(), "invalid dictionary".to_string())
+                    ])
+                });
+            };
+            let meta = dict.meta.clone();
+            let (exam, db) = common::load_exam(&data.details, &dict, db).await;
+            let exam = match exam {
+                Ok(exam) => exam,
+                Err(error) => return Json(Message {
+                    session: 0,
+                    details: HashMap::from([
+                        ("error".to_string(), error)
+                    ])
+                }),
+            };
             let session = Session::create_with(match kind {
-                "standard" => SessionInner::Standard(standard::create(db).await),
-                "recall" => SessionInner::Recall(recall::create(db, false).await),
-                "recall-tyv" => SessionInner::Recall(recall::create(db, true).await),
-                "recall-mass" => SessionInner::MassRecall(mass_recall::create(db).await),
+                "standard" => SessionInner::Standard(
+                    standard::create(db, dict, exam, fast, family_size, learner, seed).await),
+                "recall" => SessionInner::Recall(
+                    recall::create(db, dict, exam, false, fast, family_size, learner, seed).await),
+                // these kinds draw their own word lists
+                "recall-tyv" | "recall-mass" if exam.is_some() => return Json(Message {
+                    session: 0,
+                    details: HashMap::from([
+                        ("error".to_string(), "exam is not supported by this kind".to_string())
+                    ])
+                }),
+                "recall-tyv" => SessionInner::Recall(
+                    recall::create(db, dict, None, true, None, false, None, seed).await),
+                "recall-mass" => SessionInner::MassRecall(
+                    mass_recall::create(db, dict, seed).await),
                 _ => return Json(Message {
                     session: 0,
                     details: HashMap::from([
@@ -169,7 +247,12 @@ pub async fn start(data: Json<Message>, db: BaseConn) -> Json<Message> {
             let id = session.read().await.id;
             Json(Message {
                 session: id,
-                details: Default::default(),
+                details: HashMap::from([
+                    ("dictionary".to_string(), meta.name),
+                    ("source".to_string(), meta.source),
+                    ("gloss".to_string(), meta.gloss),
+                    ("seed".to_string(), seed.to_string()),
+                ]),
             })
         }
     }
@@ -178,6 +261,153 @@ pub async fn start(data: Json<Message>, db: BaseConn) -> Json<Message> {
 #[options("/start")]
 pub async fn start_options() { }
 
+#[get("/dictionaries")]
+pub async fn dictionaries(db: BaseConn) -> Json<Message> {
+    let (names, _) = common::dictionaries(db).await;
+    Json(Message {
+        session: 0,
+        details: HashMap::from([
+            ("dictionaries".to_string(), names.join(";;;")),
+        ]),
+    })
+}
+
+#[get("/words/search?<q>&<dictionary>")]
+pub async fn search_words(q: &str, dictionary: Option<&str>, db: BaseConn) -> Json<Message> {
+    let (dict, db) = common::load_dictionary(dictionary, db).await;
+    let Some(dict) = dict else { return common::error("invalid dictionary") };
+    let (found, _) = words::search(q, &dict, db).await;
+    Json(Message {
+        session: 0,
+        details: HashMap::from([
+            ("words".to_string(), found.join(";;;")),
+        ]),
+    })
+}
+
+#[get("/words/<word>?<dictionary>")]
+pub async fn word(word: &str, dictionary: Option<&str>, db: BaseConn) -> Json<Message> {
+    let (dict, db) = common::load_dictionary(dictionary, db).await;
+    let Some(dict) = dict else { return common::error("invalid dictionary") };
+    let (entry, _) = words::lookup(word, &dict, db).await;
+    Json(Message {
+        session: 0,
+        details: entry.unwrap_or_else(|| HashMap::from([
+            ("error".to_string(), "unknown word".to_string()),
+        ])),
+    })
+}
+
+/// Reads the learner a request is made for, or the error to respond with
+fn learner_context(data: &Message) -> Result<String, Json<Message>> {
+    match study::learner(&data.details) {
+        Ok(Some(learner)) => Ok(learner),
+        Ok(None) => Err(common::error("no learner specified")),
+        Err(message) => Err(common::error(&message)),
+    }
+}
+
+/// Loads the learner and dictionary of a study or progress request, or the error to respond
+/// with
+async fn study_context(
+    data: &Message, db: BaseConn
+) -> WithConn<Result<(String, common::Dictionary), Json<Message>>> {
+    let learner = match learner_context(data) {
+        Ok(learner) => learner,
+        Err(error) => return (Err(error), db),
+    };
+    let name = data.details.get("dictionary").map(|x| x.as_str());
+    let (dict, db) = common::load_dictionary(name, db).await;
+    match dict {
+        Some(dict) => (Ok((learner, dict)), db),
+        None => (Err(common::error("invalid dictionary")), db),
+    }
+}
+
+#[post("/study/due", format = "json", data = "<data>")]
+pub async fn study_due(data: Json<Message>, db: BaseConn) -> Json<Message> {
+    let (context, db) = study_context(&data, db).await;
+    match context {
+        Ok((learner, dict)) => study::due(&learner, &dict, db).await.0,
+        Err(error) => error,
+    }
+}
+
+#[post("/study/grade", format = "json", data = "<data>")]
+pub async fn study_grade(data: Json<Message>, db: BaseConn) -> Json<Message> {
+    let (context, db) = study_context(&data, db).await;
+    match context {
+        Ok((learner, dict)) => study::grade(&learner, &dict, &data.details, db).await.0,
+        Err(error) => error,
+    }
+}
+
+/// Estimates of the learner over time with their trend and implausible jumps
+#[post("/progress", format = "json", data = "<data>")]
+pub async fn learner_progress(data: Json<Message>, db: BaseConn) -> Json<Message> {
+    let (context, db) = study_context(&data, db).await;
+    match context {
+        Ok((learner, dict)) => progress::progress(&learner, &dict, db).await.0,
+        Err(error) => error,
+    }
+}
+
+/// Creates a class taught by the learner, answering with its invite code and the token its
+/// teacher assigns and reads reports with
+#[post("/classes/create", format = "json", data = "<data>")]
+pub async fn class_create(data: Json<Message>, db: BaseConn) -> Json<Message> {
+    match learner_context(&data) {
+        Ok(learner) => classroom::create(&learner, &data.details, db).await.0,
+        Err(error) => error,
+    }
+}
+
+#[post("/classes/join", format = "json", data = "<data>")]
+pub async fn class_join(data: Json<Message>, db: BaseConn) -> Json<Message> {
+    match learner_context(&data) {
+        Ok(learner) => classroom::join(&learner, &data.details, db).await.0,
+        Err(error) => error,
+    }
+}
+
+#[post("/classes/assign", format = "json", data = "<data>")]
+pub async fn class_assign(data: Json<Message>, db: BaseConn) -> Json<Message> {
+    match learner_context(&data) {
+        Ok(learner) => classroom::assign(&learner, &data.details, db).await.0,
+        Err(error) => error,
+    }
+}
+
+#[post("/classes/assignments", format = "json", data = "<data>")]
+pub async fn class_assignments(data: Json<Message>, db: BaseConn) -> Json<Message> {
+    match learner_context(&data) {
+        Ok(learner) => classroom::assignments(&learner, &data.details, db).await.0,
+        Err(error) => error,
+    }
+}
+
+/// Latest estimates and level breakdown of each student, for the teacher of the class
+#[post("/classes/report", format = "json", data = "<data>")]
+pub async fn class_report(data: Json<Message>, db: BaseConn) -> Json<Message> {
+    match learner_context(&data) {
+        Ok(learner) => classroom::report(&learner, &data.details, db).await.0,
+        Err(error) => error,
+    }
+}
+
+/// Study plan of a finished session, the "count" most frequent words not known past the
+/// estimated frontier, optionally from the "exam" list only
+#[post("/recommend", format = "json", data = "<data>")]
+pub async fn recommend_words(data: Json<Message>, db: BaseConn) -> Json<Message> {
+    let (result, _) = recommend::recommend(data.session, &data.details, db).await;
+    Json(Message {
+        session: 0,
+        details: result.unwrap_or_else(|error| HashMap::from([
+            ("error".to_string(), error),
+        ])),
+    })
+}
+
 #[get("/state", format = "json", data = "<data>")]
 pub async fn state(data: Json<Message>, db: BaseConn) -> Json<Message> {
     match Session::access(data.session).await {
@@ -186,8 +416,9 @@ pub async fn state(data: Json<Message>, db: BaseConn) -> Json<Message> {
             details: Default::default(),
         }),
         Some(ses) => {
-            let ses = ses.read().await;
-            match &ses.inner {
+            // serving a question stamps when it was asked
+            let mut ses = ses.write().await;
+            match &mut ses.inner {
                 SessionInner::Standard(ses) => standard::state(ses, db).await,
                 SessionInner::Recall(ses) => recall::state(ses, db).await,
                 SessionInner::MassRecall(ses) => mass_recall::state(ses, db).await,
@@ -204,6 +435,34 @@ pub async fn state_post(data: Json<Message>, db: BaseConn) -> Json<Message> {
 #[options("/state")]
 pub async fn state_options() { }
 
+/// Lists the distractor candidates left out of the current question and why
+#[cfg(feature = "permissive")]
+#[get("/debug/distractors", format = "json", data = "<data>")]
+pub async fn debug_distractors(data: Json<Message>) -> Json<Message> {
+    let Some(ses) = Session::access(data.session).await else {
+        return Json(Message {
+            session: 0,
+            details: Default::default(),
+        });
+    };
+    let ses = ses.read().await;
+    let details = match &ses.inner {
+        SessionInner::Standard(ses) => HashMap::from([
+            ("rejected".to_string(), ses.rejected.iter()
+                .map(|(candidate, reason)| format!("{}: {}", reason, candidate))
+                .collect::<Vec<_>>()
+                .join(";;;")),
+        ]),
+        _ => HashMap::from([
+            ("error".to_string(), "not a multiple-choice session".to_string()),
+        ]),
+    };
+    Json(Message {
+        session: 0,
+        details,
+    })
+}
+
 #[post("/submit", format = "json", data = "<data>")]
 pub async fn submit(data: Json<Message>, db: BaseConn) -> Json<Message> {
     let sid = data.session;
@@ -213,16 +472,23 @@ pub async fn submit(data: Json<Message>, db: BaseConn) -> Json<Message> {
             details: Default::default(),
         }),
         Some(ses) => {
+            let finish = data.details.get("action").is_some_and(|x| x == "finish");
             let (resp, term) = {
                 let mut ses = ses.write().await;
-                match &mut ses.inner {
+                let (resp, term) = match &mut ses.inner {
                     SessionInner::Standard(ses) => 
                         standard::submit(ses, db, data).await,
                     SessionInner::Recall(ses) =>
                         recall::submit(ses, db, data).await,
                     SessionInner::MassRecall(ses) =>
                         mass_recall::submit(ses, db, data).await,
+                };
+                if finish && !resp.details.contains_key("error") {
+                    if let Some((history, dict)) = ses.answers() {
+                        recommend::keep(sid, history.to_vec(), dict.clone()).await;
+                    }
                 }
+                (resp, term)
             };
             if term {
                 Session::terminate(sid).await;
@@ -1,4 +1,5 @@
 use std::collections::HashMap;
+use rand::{Rng, SeedableRng};
 
 use rocket::serde::json::Json;
 use rocket_db_pools::{Connection, sqlx};
@@ -7,16 +8,27 @@ use rocket_db_pools::sqlx::Row;
 use vcbe_core::Message;
 
 use crate::{Base, BaseConn, common};
+use crate::common::{Answer, Asked, Dictionary, SessionRng};
 
 pub struct Session {
-    pub history: Vec<(u32, bool)>,
+    pub history: Vec<Answer>,
     pub current_words: Vec<u32>,
+    pub pseudo: Vec<Answer>,
+    pub current_pseudo: Vec<bool>,
+    pub asked: Asked,
+    pub dict: Dictionary,
+    pub rng: SessionRng,
 }
 
-pub async fn create(db: BaseConn) -> Session {
+pub async fn create(db: BaseConn, dict: Dictionary, seed: u64) -> Session {
     let mut session = Session {
         history: Vec::new(),
         current_words: Vec::new(),
+        pseudo: Vec::new(),
+        current_pseudo: Vec::new(),
+        asked: Asked::new(),
+        dict,
+        rng: SessionRng::seed_from_u64(seed),
     };
     let _ = update(&mut session, db).await;
     session
@@ -24,18 +36,37 @@ pub async fn create(db: BaseConn) -> Session {
 
 async fn update(session: &mut Session, db: BaseConn) -> BaseConn {
     let ordinal = session.history.len();
-    let lv = (ordinal / 100) % 8;
-    session.current_words = common::choose_words(&session.history, lv);
+    let lv = (ordinal / 100) % session.dict.bands.len();
+    let (current_words, db) =
+        common::choose_words(&session.history, &session.dict, lv, &mut session.rng, db).await;
+    session.current_words = current_words;
+    session.current_pseudo = vec![false; session.current_words.len()];
+    // scatter pseudo-words among the real ones
+    let count = (session.current_words.len() as f64 * common::PSEUDO_RATE) as usize;
+    let (pseudo, db) = common::choose_pseudo(
+        &session.pseudo, &session.dict, count, &mut session.rng, db).await;
+    for word in pseudo {
+        let index = session.rng.gen_range(0..=session.current_words.len());
+        session.current_words.insert(index, word);
+        session.current_pseudo.insert(index, true);
+    }
+    session.asked = Asked::new();
     db
 }
 
-pub async fn state(session: &Session, mut db: Connection<Base>) -> Json<Message> {
-    let result_available = session.history.len() >= 800;
+pub async fn state(session: &mut Session, mut db: Connection<Base>) -> Json<Message> {
+    session.asked.serve();
+    let result_available = session.history.len() >= 100 * session.dict.bands.len();
     let mut questions = Vec::new();
-    for &word in &session.current_words {
-        let row = sqlx::query("SELECT word FROM words WHERE id = ?")
-            .bind(word).fetch_one(&mut **db).await.unwrap();
-        let word: String = row.get(0);
+    for (&word, &pseudo) in session.current_words.iter().zip(&session.current_pseudo) {
+        let word: String = if pseudo {
+            let (word, next) = common::pseudo_word(word, &session.dict, db).await;
+            db = next;
+            word
+        } else {
+            sqlx::query(&format!("SELECT word FROM {} WHERE id = ?", session.dict.words))
+                .bind(word).fetch_one(&mut **db).await.unwrap().get(0)
+        };
         questions.push(word);
     }
     Json(Message {
@@ -53,17 +84,30 @@ pub async fn submit(
     match data.details["action"].as_str() {
         "choose" => {
             let choices = data.details["choices"].split(",")
-                .map(|x| x.parse::<bool>().unwrap())
+                .map(|x| common::recall_score(x).unwrap())
                 .collect::<Vec<_>>();
-            session.history.extend(session.current_words.iter().copied().zip(choices));
-            let db = update(session, db).await;
+            // only the time spent on the whole batch is known, spread it evenly
+            let latency = session.asked.at.elapsed().as_millis() as u32
+                / session.current_words.len().max(1) as u32;
+            let answers = session.current_words.iter().copied()
+                .zip(choices)
+                .zip(session.current_pseudo.iter().copied());
+            for ((word, score), pseudo) in answers {
+                let answer = Answer { word, score, latency, listed: false };
+                if pseudo {
+                    session.pseudo.push(answer);
+                } else {
+                    session.history.push(answer);
+                }
+            }
+            update(session, db).await;
             (Json(Message {
                 session: 0,
                 details: HashMap::new()
             }), false)
         }
         "finish" => {
-            if session.history.len() < 800 {
+            if session.history.len() < 100 * session.dict.bands.len() {
                 (Json(Message {
                     session: 0,
                     details: HashMap::from([
@@ -71,8 +115,9 @@ pub async fn submit(
                     ])
                 }), false)
             } else {
-                let (details, db) = 
-                    common::result(&session.history, db).await;
+                let (mut details, _) =
+                    common::result(&session.history, None, &session.dict, None, false, db).await;
+                common::false_alarm(&mut details, &session.pseudo, &session.dict);
                 (Json(Message {
                     session: 0,
                     details,
@@ -0,0 +1,98 @@
+use std::collections::HashMap;
+use rocket::serde::json::Json;
+use rocket_db_pools::sqlx;
+use rocket_db_pools::sqlx::Row;
+use vcbe_core::Message;
+use crate::{BaseConn, WithConn};
+use crate::common::{Dictionary, Tables};
+
+const RESULTS_CREATION: &str = r#"
+create table if not exists results (
+    id integer not null auto_increment primary key,
+    learner varchar(64) not null,
+    dictionary varchar(64) not null,
+    kind varchar(16) not null,
+    finished datetime not null,
+    uls integer not null,
+    rfwls integer not null,
+    heu integer not null,
+    levels_asked text not null,
+    levels_correct text not null,
+    levels_known text not null,
+    index (learner, dictionary, finished)
+);
+"#;
+
+pub static RESULTS: Tables = Tables::new(&[RESULTS_CREATION]);
+
+/// Stores the result of a finished session of a learner
+pub async fn record(
+    learner: Option<&str>, dict: &Dictionary, kind: &str, result: &HashMap<String, String>,
+    db: BaseConn
+) -> BaseConn {
+    let Some(learner) = learner else { return db };
+    let mut db = RESULTS.ensure(db).await;
+    let estimate = |key: &str| result.get(key).and_then(|x| x.parse::<u32>().ok()).unwrap_or(0);
+    let levels = |key: &str| result.get(key).cloned().unwrap_or_default();
+    sqlx::query("insert into results (learner, dictionary, kind, finished, uls, rfwls, heu,
+                 levels_asked, levels_correct, levels_known)
+                 values (?, ?, ?, NOW(), ?, ?, ?, ?, ?, ?)")
+        .bind(learner).bind(&dict.meta.name).bind(kind)
+        .bind(estimate("uls")).bind(estimate("rfwls")).bind(estimate("heu"))
+        .bind(levels("levels_asked")).bind(levels("levels_correct")).bind(levels("levels_known"))
+        .execute(&mut **db).await.unwrap();
+    db
+}
+
+/// Latest result of a learner on a dictionary, keyed as in the finish response with the time
+/// it was finished in seconds since the epoch
+pub async fn latest(
+    learner: &str, dictionary: &str, db: BaseConn
+) -> WithConn<Option<HashMap<String, String>>> {
+    let mut db = RESULTS.ensure(db).await;
+    let row = sqlx::query("SELECT CAST(UNIX_TIMESTAMP(finished) AS SIGNED), uls, rfwls, heu,
+                           levels_asked, levels_correct, levels_known FROM results
+                           WHERE learner = ? AND dictionary = ? ORDER BY finished DESC, id DESC
+                           LIMIT 1")
+        .bind(learner).bind(dictionary)
+        .fetch_optional(&mut **db).await.unwrap();
+    let latest = row.map(|row| HashMap::from([
+        ("finished".to_string(), row.get::<i64, _>(0).to_string()),
+        ("uls".to_string(), row.get::<i32, _>(1).to_string()),
+        ("rfwls".to_string(), row.get::<i32, _>(2).to_string()),
+        ("heu".to_string(), row.get::<i32, _>(3).to_string()),
+        ("levels_asked".to_string(), row.get(4)),
+        ("levels_correct".to_string(), row.get(5)),
+        ("levels_known".to_string(), row.get(6)),
+    ]));
+    (latest, db)
+}
+
+/// ULS estimates of a learner over time with their trend, the growth rate in words per day
+/// and the indices of the estimates that jumped implausibly
+pub async fn progress(learner: &str, dict: &Dictionary, db: BaseConn) -> WithConn<Json<Message>> {
+    let mut db = RESULTS.ensure(db).await;
+    let rows = sqlx::query("SELECT CAST(UNIX_TIMESTAMP(finished) AS SIGNED), uls FROM results
+                            WHERE learner = ? AND dictionary = ? ORDER BY finished, id")
+        .bind(learner).bind(&dict.meta.name)
+        .fetch_all(&mut **db).await.unwrap();
+    let points = rows.iter()
+        .map(|row| (row.get::<i64, _>(0), row.get::<i32, _>(1)))
+        .collect::<Vec<_>>();
+    let first = points.first().map_or(0, |x| x.0);
+    let trend = vcbe_core::trend(&points.iter()
+        .map(|(time, uls)| ((time - first) as f64 / 86400.0, *uls as f64))
+        .collect::<Vec<_>>());
+    let join = |x: Vec<String>| x.join(",");
+    let details = HashMap::from([
+        ("times".to_string(), join(points.iter().map(|x| x.0.to_string()).collect())),
+        ("uls".to_string(), join(points.iter().map(|x| x.1.to_string()).collect())),
+        ("trend".to_string(), join(trend.smoothed.iter()
+            .map(|x| (x.round() as i64).to_string()).collect())),
+        ("growth".to_string(), format!("{:.1}", trend.growth)),
+        ("jumps".to_string(), join(trend.jumps.iter().enumerate()
+            .filter(|(_, jump)| **jump)
+            .map(|(i, _)| i.to_string()).collect())),
+    ]);
+    (Json(Message { session: 0, details }), db)
+}
@@ -1,6 +1,6 @@
-use std::collections::{BTreeMap, HashMap};
+use std::collections::HashMap;
 use once_cell::sync::Lazy;
-use rand::{Rng, thread_rng};
+use rand::{Rng, SeedableRng};
 
 use rocket::serde::json::Json;
 use rocket_db_pools::{Connection, sqlx};
@@ -8,19 +8,48 @@ use rocket_db_pools::sqlx::Row;
 
 use vcbe_core::{Message, TyvData};
 
-use crate::{Base, BaseConn, common};
+use crate::{Base, BaseConn, common, progress, study};
+use crate::common::{Answer, Asked, Dictionary, ExamList, SessionRng};
 
 pub struct Session {
-    pub history: Vec<(u32, bool)>,
+    pub history: Vec<Answer>,
     pub current_word: u32,
+    /// Whether the current word was drawn from the exam list
+    pub current_listed: bool,
     pub tyv_mode: bool,
+    pub pseudo: Vec<Answer>,
+    pub current_pseudo: bool,
+    pub asked: Asked,
+    pub fast_threshold: Option<u32>,
+    pub family_size: bool,
+    pub learner: Option<String>,
+    /// Whether a finish already recorded the result, later ones only report it again
+    pub recorded: bool,
+    pub dict: Dictionary,
+    pub exam: Option<ExamList>,
+    pub rng: SessionRng,
 }
 
-pub async fn create(db: BaseConn, tyv_mode: bool) -> Session {
+#[allow(clippy::too_many_arguments)]
+pub async fn create(
+    db: BaseConn, dict: Dictionary, exam: Option<ExamList>, tyv_mode: bool,
+    fast_threshold: Option<u32>, family_size: bool, learner: Option<String>, seed: u64
+) -> Session {
     let mut session = Session {
         history: Vec::new(),
         current_word: 0,
-        tyv_mode
+        current_listed: false,
+        tyv_mode,
+        pseudo: Vec::new(),
+        current_pseudo: false,
+        asked: Asked::new(),
+        fast_threshold,
+        family_size,
+        learner,
+        recorded: false,
+        dict,
+        exam,
+        rng: SessionRng::seed_from_u64(seed),
     };
     let _ = update(&mut session, db).await;
     session
@@ -51,29 +80,50 @@ static TYV_DATA: Lazy<TyvData> = Lazy::new(|| {
 
 async fn update(session: &mut Session, db: BaseConn) -> BaseConn {
     let ordinal = session.history.len();
+    session.current_pseudo = false;
+    session.current_listed = false;
+    session.asked = Asked::new();
     if session.tyv_mode {
         let range = if ordinal < 40 {
             0..TYV_BROAD.ito.len()
         } else {
             TYV_BROAD.ito.len()..TYV_BROAD.ito.len() + TYV_NARROW.ito.len()
         };
-        let mut word = thread_rng().gen_range(range.clone());
-        while session.history.iter().any(|(x, _)| *x == word as u32) {
-            word = thread_rng().gen_range(range.clone());
+        let mut word = session.rng.gen_range(range.clone());
+        while session.history.iter().any(|x| x.word == word as u32) {
+            word = session.rng.gen_range(range.clone());
         }
         session.current_word = word as u32;
         return db;
     }
-    let lv = if ordinal < 24 {
+    let warmup = common::warmup(&session.dict);
+    let lv = if ordinal < warmup {
         ordinal / 3
     } else {
-        ((ordinal - 24) / 2) % 8
+        ((ordinal - warmup) / 2) % session.dict.bands.len()
     };
-    session.current_word = common::choose_word(&session.history, lv);
+    let db = if session.rng.gen_bool(common::PSEUDO_RATE) {
+        let (pseudo, db) = common::choose_pseudo(
+            &session.pseudo, &session.dict, 1, &mut session.rng, db).await;
+        // a real word is asked instead once the pseudo-words run out
+        if let Some(word) = pseudo.first() {
+            session.current_word = *word;
+            session.current_pseudo = true;
+            return db;
+        }
+        db
+    } else {
+        db
+    };
+    let ((current_word, listed), db) = common::choose_word(
+        &session.history, &session.dict, session.exam.as_ref(), lv, &mut session.rng, db).await;
+    session.current_word = current_word;
+    session.current_listed = listed;
     db
 }
 
-pub async fn state(session: &Session, mut db: Connection<Base>) -> Json<Message> {
+pub async fn state(session: &mut Session, mut db: Connection<Base>) -> Json<Message> {
+    session.asked.serve();
     if session.tyv_mode {
         let result_available = session.history.len() >= 60;
         let broad_ito_len = TYV_BROAD.ito.len();
@@ -90,10 +140,13 @@ pub async fn state(session: &Session, mut db: Connection<Base>) -> Json<Message>
             ])
         });
     }
-    let result_available = session.history.len() >= 24;
-    let row = sqlx::query("SELECT word FROM words WHERE id = ?")
-        .bind(session.current_word).fetch_one(&mut **db).await.unwrap();
-    let word: String = row.get(0);
+    let result_available = session.history.len() >= common::warmup(&session.dict);
+    let word: String = if session.current_pseudo {
+        common::pseudo_word(session.current_word, &session.dict, db).await.0
+    } else {
+        sqlx::query(&format!("SELECT word FROM {} WHERE id = ?", session.dict.words))
+            .bind(session.current_word).fetch_one(&mut **db).await.unwrap().get(0)
+    };
     Json(Message {
         session: 0,
         details: HashMap::from([
@@ -108,16 +161,35 @@ pub async fn submit(
 ) -> (Json<Message>, bool) {
     match data.details["action"].as_str() {
         "choose" => {
-            let choice = data.details["recall"] == "true";
-            session.history.push((session.current_word, choice));
-            let db = update(session, db).await;
+            let Some(score) = data.details.get("recall")
+                .and_then(|x| common::recall_score(x)) else {
+                return (Json(Message {
+                    session: 0,
+                    details: HashMap::from([
+                        ("error".to_string(), "invalid recall".to_string())
+                    ])
+                }), false);
+            };
+            let answer = Answer {
+                word: session.current_word,
+                score,
+                latency: session.asked.at.elapsed().as_millis() as u32,
+                listed: session.current_listed,
+            };
+            if session.current_pseudo {
+                session.pseudo.push(answer);
+            } else {
+                session.history.push(answer);
+            }
+            update(session, db).await;
             (Json(Message {
                 session: 0,
                 details: HashMap::new()
             }), false)
         }
         "finish" => {
-            if session.history.len() < 24 || (session.tyv_mode && session.history.len() < 60) {
+            let warmup = if session.tyv_mode { 60 } else { common::warmup(&session.dict) };
+            if session.history.len() < warmup {
                 (Json(Message {
                     session: 0,
                     details: HashMap::from([
@@ -125,10 +197,23 @@ pub async fn submit(
                     ])
                 }), false)
             } else {
-                let (details, db) = if session.tyv_mode {
+                let (details, _) = if session.tyv_mode {
                     (tyv_result(&session.history), db)
                 } else {
-                    common::result(&session.history, db).await
+                    let (mut details, db) = common::result(
+                        &session.history, session.fast_threshold, &session.dict,
+                        session.exam.as_ref(), session.family_size, db).await;
+                    common::false_alarm(&mut details, &session.pseudo, &session.dict);
+                    if session.recorded {
+                        (details, db)
+                    } else {
+                        session.recorded = true;
+                        let learner = session.learner.as_deref();
+                        let db = study::seed(learner, &session.dict, &session.history, db).await;
+                        let db = progress::record(
+                            learner, &session.dict, "recall", &details, db).await;
+                        (details, db)
+                    }
                 };
                 (Json(Message {
                     session: 0,
@@ -140,16 +225,16 @@ pub async fn submit(
     }
 }
 
-fn tyv_result(history: &[(u32, bool)]) -> HashMap<String, String> {
+fn tyv_result(history: &[Answer]) -> HashMap<String, String> {
     let broad_len = TYV_BROAD.ito.len() as u32;
     let result = history.iter()
-        .map(|(i, r)| {
-            let word = if *i < broad_len {
-                &TYV_BROAD.ito[*i as usize]
+        .map(|x| {
+            let word = if x.word < broad_len {
+                &TYV_BROAD.ito[x.word as usize]
             } else {
-                &TYV_NARROW.ito[*i as usize - broad_len as usize]
+                &TYV_NARROW.ito[x.word as usize - broad_len as usize]
             };
-            (&word as &str, *r)
+            (word as &str, x.score)
         })
         .collect::<Vec<_>>();
     let est_tyv = vcbe_core::estimate_tyv(&result, &TYV_DATA);
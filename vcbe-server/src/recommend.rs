@@ -0,0 +1,86 @@
+use std::collections::{BTreeMap, HashMap};
+use std::time::Instant;
+use once_cell::sync::Lazy;
+use rocket::tokio::sync::RwLock;
+use rocket_db_pools::sqlx;
+use rocket_db_pools::sqlx::Row;
+use crate::{BaseConn, WithConn};
+use crate::common::{self, Answer, Dictionary};
+
+/// Answers of the sessions finished recently, kept after the session ends so that a study
+/// plan can still be asked for
+struct Finished {
+    at: Instant,
+    history: Vec<Answer>,
+    dict: Dictionary,
+}
+
+static FINISHED: Lazy<RwLock<BTreeMap<u32, Finished>>> =
+    Lazy::new(|| RwLock::new(BTreeMap::new()));
+
+/// Words recommended when the request does not ask for a number
+pub const DEFAULT_COUNT: usize = 20;
+
+/// Most words recommended at once
+pub const MAX_COUNT: usize = 100;
+
+pub async fn keep(session: u32, history: Vec<Answer>, dict: Dictionary) {
+    FINISHED.write().await.insert(session, Finished { at: Instant::now(), history, dict });
+}
+
+/// Forgets the sessions finished more than `secs` seconds ago
+pub async fn expire(secs: u64) {
+    FINISHED.write().await.retain(|_, x| x.at.elapsed().as_secs() <= secs);
+}
+
+/// The most frequent words not known after a finished session, out of the missed words and
+/// the unasked words from the frontier on, optionally only the ones of an exam list
+pub async fn recommend(
+    session: u32, details: &HashMap<String, String>, db: BaseConn
+) -> WithConn<Result<HashMap<String, String>, String>> {
+    let finished = FINISHED.read().await.get(&session)
+        .map(|x| (x.history.clone(), x.dict.clone()));
+    let Some((history, dict)) = finished else {
+        return (Err("no finished session".to_string()), db);
+    };
+    let count = match details.get("count").map(|x| x.parse::<usize>()) {
+        None => DEFAULT_COUNT,
+        Some(Ok(count)) if (1..=MAX_COUNT).contains(&count) => count,
+        Some(_) => return (Err("invalid count".to_string()), db),
+    };
+    let (evidences, mut db) = common::evidences(&history, &dict, db).await;
+    let evidences = common::uniform_draws(&evidences, &history);
+    let frontier = vcbe_core::frontier(&evidences, &dict.bands);
+    let missed = history.iter()
+        .filter(|x| x.score < 0.5)
+        .map(|x| x.word.to_string())
+        .chain(std::iter::once("-1".to_string()))
+        .collect::<Vec<_>>()
+        .join(",");
+    let exam = details.get("exam").map(|x| x.to_uppercase());
+    let filter = match exam {
+        Some(_) => format!("AND FIND_IN_SET(?, {})", dict.column("exam")),
+        None => String::new(),
+    };
+    let query = format!(
+        "SELECT id, word FROM {} WHERE (id IN ({}) OR id >= ?) {} ORDER BY id LIMIT ?",
+        dict.words, missed, filter);
+    let mut query = sqlx::query(&query).bind(frontier);
+    if let Some(exam) = &exam {
+        query = query.bind(exam);
+    }
+    // correctly answered words past the frontier are dropped afterward
+    let rows = query.bind((count + history.len()) as u32)
+        .fetch_all(&mut **db).await.unwrap();
+    let (ids, words): (Vec<_>, Vec<_>) = rows.iter()
+        .map(|row| (row.get::<i32, _>(0) as u32, row.get::<String, _>(1)))
+        .filter(|(id, _)| !history.iter().any(|x| x.word == *id && x.score >= 0.5))
+        .take(count)
+        .unzip();
+    let result = HashMap::from([
+        ("frontier".to_string(), frontier.to_string()),
+        ("ids".to_string(), ids.iter().map(|x| x.to_string()).collect::<Vec<_>>().join(",")),
+        ("words".to_string(), words.join(";;;")),
+    ]);
+    (Ok(result), db)
+}
@@ -1,34 +1,61 @@
 use std::collections::HashMap;
 use rand::prelude::*;
+use rand::seq::index::sample;
 use rocket::serde::json::Json;
 use rocket_db_pools::{Connection, sqlx};
 use rocket_db_pools::sqlx::Row;
-use vcbe_core::{LV_RANGES, Message};
-use crate::{Base, BaseConn, common, WithConn};
+use vcbe_core::{Definition, family_root, Message};
+use crate::{Base, BaseConn, common, progress, study, WithConn};
+use crate::common::{Asked, Dictionary, ExamList, SessionRng};
 
 pub struct Session {
-    pub history: Vec<(u32, bool)>,
+    pub history: Vec<common::Answer>,
     pub current_word: u32,
+    /// Whether the current word was drawn from the exam list
+    pub current_listed: bool,
     pub question: String,
     pub candidates: Vec<String>,
     pub answer: usize,
+    pub rejected: Vec<(String, &'static str)>,
+    pub asked: Asked,
+    pub fast_threshold: Option<u32>,
+    pub family_size: bool,
+    pub learner: Option<String>,
+    pub dict: Dictionary,
+    pub exam: Option<ExamList>,
+    pub rng: SessionRng,
 }
 
-pub async fn create(db: BaseConn) -> Session {
+pub async fn create(
+    db: BaseConn, dict: Dictionary, exam: Option<ExamList>, fast_threshold: Option<u32>,
+    family_size: bool, learner: Option<String>, seed: u64
+) -> Session {
     let mut session = Session {
         history: Vec::new(),
         current_word: 0,
+        current_listed: false,
         question: "".to_string(),
         candidates: Vec::new(),
         answer: 0,
+        rejected: Vec::new(),
+        asked: Asked::new(),
+        fast_threshold,
+        family_size,
+        learner,
+        dict,
+        exam,
+        rng: SessionRng::seed_from_u64(seed),
     };
     let _ = update(&mut session, db).await;
     session
 }
 
-async fn related(word: u32, mut db: BaseConn) -> WithConn<Vec<u32>> {
-    let row = sqlx::query(
-        "SELECT sim, incl, incl_rev, lv FROM words WHERE id = ?")
+async fn related(
+    word: u32, dict: &Dictionary, rng: &mut SessionRng, mut db: BaseConn
+) -> WithConn<Vec<u32>> {
+    let row = sqlx::query(&format!(
+        "SELECT sim, incl, incl_rev, lv, {} FROM {} WHERE id = ?",
+        dict.column("sound"), dict.words))
         .bind(word)
         .fetch_one(&mut **db).await.unwrap();
     let sim: Vec<u32> = {
@@ -43,11 +70,20 @@ async fn related(word: u32, mut db: BaseConn) -> WithConn<Vec<u32>> {
         let incl_rev: String = row.get(2);
         incl_rev.split(',').filter_map(|x| x.parse().ok()).collect()
     };
-    let mut related: Vec<u32> = sim.into_iter().chain(incl).chain(incl_rev).collect();
+    let sound: Vec<u32> = {
+        let sound: String = row.get(4);
+        sound.split(',').filter_map(|x| x.parse().ok()).collect()
+    };
+    // sound-alikes come right after look-alikes, both are tempting distractors
+    let mut related: Vec<u32> = sim.into_iter()
+        .chain(sound)
+        .chain(incl)
+        .chain(incl_rev)
+        .collect();
     if related.len() < 5 {
         let lv: u32 = row.get::<i32, _>(3) as u32;
         while related.len() < 5 {
-            let new_word = thread_rng().gen_range(LV_RANGES[lv as usize].clone());
+            let new_word = rng.gen_range(dict.bands.ranges[lv as usize].clone());
             if !related.contains(&new_word) && new_word != word {
                 related.push(new_word);
             }
@@ -56,79 +92,187 @@ async fn related(word: u32, mut db: BaseConn) -> WithConn<Vec<u32>> {
     (related, db)
 }
 
-async fn gen_en2cn(
-    word: u32, related: &[u32], mut db: BaseConn
-) -> WithConn<(Vec<String>, usize)> {
-    let mut sim_des = Vec::new();
-    for &wi in related {
-        let d: String = sqlx::query(
-            "SELECT des FROM words WHERE id = ?")
-            .bind(wi)
-            .fetch_one(&mut **db).await.unwrap()
-            .get(0);
-        sim_des.extend(d.split(";;;")
-            .map(|x| x.to_string()));
-    }
-    let real_des: Vec<String> = sqlx::query(
-        "SELECT des FROM words WHERE id = ?")
+/// Picks `count` candidates at random, taking the ones marked as preferred first
+fn prefer<T: Clone>(candidates: &[(T, bool)], count: usize, rng: &mut SessionRng) -> Vec<T> {
+    let (preferred, others): (Vec<_>, Vec<_>) = candidates.iter().partition(|x| x.1);
+    let mut chosen = preferred.choose_multiple(rng, count)
+        .map(|x| x.0.clone())
+        .collect::<Vec<_>>();
+    let rest = count - chosen.len();
+    chosen.extend(others.choose_multiple(rng, rest).map(|x| x.0.clone()));
+    chosen.shuffle(rng);
+    chosen
+}
+
+/// A generated multiple-choice question
+pub struct Question {
+    pub question: String,
+    pub candidates: Vec<String>,
+    pub answer: usize,
+    /// Distractor candidates left out, with the reason
+    pub rejected: Vec<(String, &'static str)>,
+}
+
+/// Number of distractors in each question
+const DISTRACTORS: usize = 3;
+
+/// Upper bound of random words tried from each level when the related ones do not give enough
+/// distractors
+const FILLERS: usize = 50;
+
+/// Words sharing a headword belong to the same family and give each other away
+fn same_family(word: &str, head: &str, other_word: &str, other_head: &str) -> bool {
+    family_root(word, head) == family_root(other_word, other_head)
+}
+
+struct WordRow {
+    word: String,
+    head: String,
+    des: Vec<Definition>,
+    lv: usize,
+}
+
+async fn fetch(word: u32, dict: &Dictionary, mut db: BaseConn) -> WithConn<WordRow> {
+    let row = sqlx::query(&format!(
+        "SELECT word, {}, des, lv FROM {} WHERE id = ?", dict.column("head"), dict.words))
         .bind(word)
-        .fetch_one(&mut **db).await.unwrap()
-        .get::<String, _>(0)
-        .split(";;;")
+        .fetch_one(&mut **db).await.unwrap();
+    let des: String = row.get(2);
+    let target = WordRow {
+        word: row.get(0),
+        head: row.get(1),
+        des: des.split(";;;").map(Definition::parse).collect(),
+        lv: row.get::<i32, _>(3) as usize,
+    };
+    (target, db)
+}
+
+/// Related words first, then random words of the level while `needed` says so, moving on to
+/// the nearest other levels when a level runs out
+fn candidate_pool(
+    word: u32, related: &[u32], dict: &Dictionary, lv: usize
+) -> impl FnMut(bool, &mut SessionRng) -> Option<u32> {
+    let mut pool = related.to_vec();
+    let mut index = 0;
+    let mut levels = dict.bands.ranges.iter().cloned().enumerate().collect::<Vec<_>>();
+    levels.sort_by_key(|(x, _)| x.abs_diff(lv));
+    let mut levels = levels.into_iter().map(|(_, range)| range);
+    let mut fillers: Vec<u32> = Vec::new();
+    move |needed: bool, rng: &mut SessionRng| {
+        if index == pool.len() {
+            if !needed {
+                return None;
+            }
+            let new_word = loop {
+                match fillers.pop() {
+                    Some(x) if x == word || pool.contains(&x) => continue,
+                    Some(x) => break x,
+                    None => {
+                        let range = levels.next()?;
+                        fillers = sample(rng, range.len(), FILLERS.min(range.len()))
+                            .into_iter()
+                            .map(|x| range.start + x as u32)
+                            .collect();
+                    }
+                }
+            };
+            pool.push(new_word);
+        }
+        index += 1;
+        Some(pool[index - 1])
+    }
+}
+
+/// Asks for the gloss of a word, distractors of the same part of speech are preferred as
+/// mismatching ones give the answer away
+async fn gen_word2gloss(
+    word: u32, related: &[u32], dict: &Dictionary, rng: &mut SessionRng, db: BaseConn
+) -> WithConn<Question> {
+    let (target, mut db) = fetch(word, dict, db).await;
+    let shown = target.des.choose(rng).unwrap().clone();
+    let mut rejected = Vec::new();
+    let mut candidates: Vec<(Definition, bool)> = Vec::new();
+    let mut pool = candidate_pool(word, related, dict, target.lv);
+    while let Some(wi) = pool(candidates.len() < DISTRACTORS, rng) {
+        let (other, next) = fetch(wi, dict, db).await;
+        db = next;
+        if same_family(&target.word, &target.head, &other.word, &other.head) {
+            rejected.push((other.word, "same headword"));
+            continue;
+        }
+        let mut glosses = Vec::new();
+        for des in other.des {
+            if target.des.iter().any(|x| x.text == des.text) {
+                rejected.push((des.to_string(), "same definition"));
+            } else if candidates.iter().any(|(x, _)| x.text == des.text) {
+                rejected.push((des.to_string(), "duplicate"));
+            } else {
+                let same_pos = des.pos_class() == shown.pos_class();
+                glosses.push((des, same_pos));
+            }
+        }
+        // one gloss per word, several glosses of a word would stand out together
+        if let Some(des) = prefer(&glosses, 1, rng).pop() {
+            let same_pos = des.pos_class() == shown.pos_class();
+            candidates.push((des, same_pos));
+        }
+    }
+    let mut candidates = prefer(&candidates, DISTRACTORS, rng).iter()
         .map(|x| x.to_string())
-        .collect();
-    let mut choices: Vec<String> = sim_des
-        .choose_multiple(&mut thread_rng(), 3)
-        .cloned().collect();
-    let correct_index = thread_rng().gen_range(0..4);
-    choices.insert(correct_index, real_des.choose(&mut thread_rng()).unwrap().clone());
-    ((choices, correct_index), db)
+        .collect::<Vec<_>>();
+    let answer = rng.gen_range(0..=candidates.len());
+    candidates.insert(answer, shown.to_string());
+    let question = Question { question: target.word, candidates, answer, rejected };
+    (question, db)
 }
 
-async fn gen_cn2en(
-    word: u32, related: &[u32], mut db: BaseConn
-) -> WithConn<(Vec<String>, usize, String)> {
-    let mut sim_word = Vec::new();
-    for &wi in related {
-        let w: String = sqlx::query(
-            "SELECT word FROM words WHERE id = ?")
-            .bind(wi)
-            .fetch_one(&mut **db).await.unwrap()
-            .get(0);
-        sim_word.push(w);
+/// Asks for the word of a gloss, distractors with a sense of the same part of speech are
+/// preferred
+async fn gen_gloss2word(
+    word: u32, related: &[u32], dict: &Dictionary, rng: &mut SessionRng, db: BaseConn
+) -> WithConn<Question> {
+    let (target, mut db) = fetch(word, dict, db).await;
+    let shown = target.des.choose(rng).unwrap().clone();
+    let mut rejected = Vec::new();
+    let mut candidates: Vec<(String, bool)> = Vec::new();
+    let mut pool = candidate_pool(word, related, dict, target.lv);
+    while let Some(wi) = pool(candidates.len() < DISTRACTORS, rng) {
+        let (other, next) = fetch(wi, dict, db).await;
+        db = next;
+        if same_family(&target.word, &target.head, &other.word, &other.head) {
+            rejected.push((other.word, "same headword"));
+        } else if other.des.iter().any(|x| x.text == shown.text) {
+            rejected.push((other.word, "same definition"));
+        } else if candidates.iter().any(|(x, _)| *x == other.word) {
+            rejected.push((other.word, "duplicate"));
+        } else {
+            let same_pos = other.des.iter().any(|x| x.pos_class() == shown.pos_class());
+            candidates.push((other.word, same_pos));
+        }
     }
-    let real_word: String = sqlx::query(
-        "SELECT word FROM words WHERE id = ?")
-        .bind(word)
-        .fetch_one(&mut **db).await.unwrap()
-        .get(0);
-    let mut choices: Vec<String> = sim_word
-        .choose_multiple(&mut thread_rng(), 3)
-        .cloned().collect();
-    let correct_index = thread_rng().gen_range(0..4);
-    choices.insert(correct_index, real_word.clone());
-    let real_des: String = sqlx::query(
-        "SELECT des FROM words WHERE id = ?")
-        .bind(word)
-        .fetch_one(&mut **db).await.unwrap()
-        .get(0);
-    let real_des = real_des.split(";;;").collect::<Vec<&str>>();
-    let real_des = real_des.choose(&mut thread_rng()).unwrap().to_string();
-    ((choices, correct_index, real_des), db)
+    let mut candidates = prefer(&candidates, DISTRACTORS, rng);
+    let answer = rng.gen_range(0..=candidates.len());
+    candidates.insert(answer, target.word);
+    let question = Question { question: shown.to_string(), candidates, answer, rejected };
+    (question, db)
 }
 
-pub async fn state(session: &Session, mut db: Connection<Base>) -> Json<Message> {
-    let result_available = session.history.len() >= 24;
+pub async fn state(session: &mut Session, _db: Connection<Base>) -> Json<Message> {
+    session.asked.serve();
+    let result_available = session.history.len() >= common::warmup(&session.dict);
     let question = session.question.clone();
     let candidates = session.candidates.clone();
-    let mut details = HashMap::from([
+    let details = HashMap::from([
         ("result_available".to_string(), result_available.to_string()),
         ("question".to_string(), question),
         ("candidates".to_string(), candidates.join(";;;")),
     ]);
-    #[cfg(feature = "permissive")] {
+    #[cfg(feature = "permissive")]
+    let details = {
+        let mut details = details;
         details.insert("answer".to_string(), session.answer.to_string());
-    }
+        details
+    };
     Json(Message {
         session: 0,
         details,
@@ -156,7 +300,11 @@ pub async fn submit(
                 Some(choice) => {
                     let choice: usize = choice.parse().unwrap();
                     let correct = session.answer == choice;
-                    session.history.push((session.current_word, correct));
+                    session.history.push(
+                        common::Answer {
+                            listed: session.current_listed,
+                            ..common::Answer::new(session.current_word, correct, session.asked.at)
+                        });
                     update(session, db).await;
                     (Json(Message {
                         session: 0,
@@ -167,7 +315,7 @@ pub async fn submit(
                 }
             }
             "finish" => {
-                if session.history.len() < 24 {
+                if session.history.len() < common::warmup(&session.dict) {
                     (Json(Message {
                         session: 0,
                         details: HashMap::from([
@@ -176,7 +324,12 @@ pub async fn submit(
                     }), false)
                 } else {
                     let (result, db) = common::result(
-                        &session.history, db).await;
+                        &session.history, session.fast_threshold, &session.dict,
+                        session.exam.as_ref(), session.family_size, db).await;
+                    let db = study::seed(
+                        session.learner.as_deref(), &session.dict, &session.history, db).await;
+                    progress::record(
+                        session.learner.as_deref(), &session.dict, "standard", &result, db).await;
                     (Json(Message {
                         session: 0,
                         details: result,
@@ -195,29 +348,27 @@ pub async fn submit(
 
 async fn update(session: &mut Session, db: BaseConn) -> BaseConn {
     let ordinal = session.history.len();
-    let (lv, is_cn2en) = if ordinal < 24 {
+    let warmup = common::warmup(&session.dict);
+    let (lv, is_gloss2word) = if ordinal < warmup {
         (ordinal / 3, ordinal % 3 == 1)
     } else {
-        (((ordinal - 24) / 2) % 8, ordinal % 2 == 1)
+        (((ordinal - warmup) / 2) % session.dict.bands.len(), ordinal % 2 == 1)
     };
-    let current_word = common::choose_word(&session.history, lv);
-    let (related, mut db) = related(current_word, db).await;
-    let (question, candidates, answer, db) = if is_cn2en {
-        let ((candidates, answer, question), db) = 
-            gen_cn2en(current_word, &related, db).await;
-        (question, candidates, answer, db)
+    let rng = &mut session.rng;
+    let ((current_word, listed), db) = common::choose_word(
+        &session.history, &session.dict, session.exam.as_ref(), lv, rng, db).await;
+    let (related, db) = related(current_word, &session.dict, rng, db).await;
+    let (question, db) = if is_gloss2word {
+        gen_gloss2word(current_word, &related, &session.dict, rng, db).await
     } else {
-        let question = sqlx::query(
-            "SELECT word FROM words WHERE id = ?")
-            .bind(current_word)
-            .fetch_one(&mut **db).await.unwrap().get(0);
-        let ((candidates, answer), db) =
-            gen_en2cn(current_word, &related, db).await;
-        (question, candidates, answer, db)
+        gen_word2gloss(current_word, &related, &session.dict, rng, db).await
     };
     session.current_word = current_word;
-    session.question = question;
-    session.candidates = candidates;
-    session.answer = answer;
+    session.current_listed = listed;
+    session.question = question.question;
+    session.candidates = question.candidates;
+    session.answer = question.answer;
+    session.rejected = question.rejected;
+    session.asked = Asked::new();
     db
 }
\ No newline at end of file
@@ -0,0 +1,112 @@
+use std::collections::HashMap;
+use rocket::serde::json::Json;
+use rocket_db_pools::sqlx;
+use rocket_db_pools::sqlx::Row;
+use vcbe_core::{Message, Review};
+use crate::{BaseConn, WithConn};
+use crate::common::{Answer, Dictionary, error, Tables};
+
+const REVIEWS_CREATION: &str = r#"
+create table if not exists reviews (
+    learner varchar(64) not null,
+    dictionary varchar(64) not null,
+    word integer not null,
+    ease double not null,
+    review_interval integer not null,
+    repetitions integer not null,
+    due datetime not null,
+    primary key (learner, dictionary, word)
+);
+"#;
+
+static REVIEWS: Tables = Tables::new(&[REVIEWS_CREATION]);
+
+/// Reads the "learner" option, an opaque id chosen by the client
+pub fn learner(details: &HashMap<String, String>) -> Result<Option<String>, String> {
+    match details.get("learner") {
+        None => Ok(None),
+        Some(x) if !x.is_empty() && x.len() <= 64 => Ok(Some(x.clone())),
+        Some(_) => Err("invalid learner".to_string()),
+    }
+}
+
+/// Adds the words missed in a finished session as new review items due right away, words
+/// already being reviewed keep their schedule
+pub async fn seed(
+    learner: Option<&str>, dict: &Dictionary, history: &[Answer], db: BaseConn
+) -> BaseConn {
+    let Some(learner) = learner else { return db };
+    let mut db = REVIEWS.ensure(db).await;
+    let review = Review::default();
+    for word in history.iter().filter(|x| x.score < 0.5).map(|x| x.word) {
+        sqlx::query("insert ignore into reviews (learner, dictionary, word, ease,
+                     review_interval, repetitions, due) values (?, ?, ?, ?, ?, ?, NOW())")
+            .bind(learner).bind(&dict.meta.name).bind(word)
+            .bind(review.ease).bind(review.interval).bind(review.repetitions)
+            .execute(&mut **db).await.unwrap();
+    }
+    db
+}
+
+/// The review item due the earliest, with the number of items due
+pub async fn due(learner: &str, dict: &Dictionary, db: BaseConn) -> WithConn<Json<Message>> {
+    let mut db = REVIEWS.ensure(db).await;
+    let remaining: i64 = sqlx::query("SELECT COUNT(*) FROM reviews
+                                      WHERE learner = ? AND dictionary = ? AND due <= NOW()")
+        .bind(learner).bind(&dict.meta.name)
+        .fetch_one(&mut **db).await.unwrap().get(0);
+    let row = sqlx::query("SELECT word FROM reviews
+                           WHERE learner = ? AND dictionary = ? AND due <= NOW()
+                           ORDER BY due LIMIT 1")
+        .bind(learner).bind(&dict.meta.name)
+        .fetch_optional(&mut **db).await.unwrap();
+    let mut details = HashMap::from([
+        ("remaining".to_string(), remaining.to_string()),
+    ]);
+    if let Some(row) = row {
+        let id: i32 = row.get(0);
+        let row = sqlx::query(&format!("SELECT word, des FROM {} WHERE id = ?", dict.words))
+            .bind(id)
+            .fetch_one(&mut **db).await.unwrap();
+        details.insert("id".to_string(), id.to_string());
+        details.insert("word".to_string(), row.get(0));
+        details.insert("desc".to_string(), row.get(1));
+    }
+    (Json(Message { session: 0, details }), db)
+}
+
+/// Reschedules a review item with a grade from 0 to 5
+pub async fn grade(
+    learner: &str, dict: &Dictionary, details: &HashMap<String, String>, db: BaseConn
+) -> WithConn<Json<Message>> {
+    let mut db = REVIEWS.ensure(db).await;
+    let Some(word) = details.get("id").and_then(|x| x.parse::<u32>().ok()) else {
+        return (error("invalid id"), db);
+    };
+    let Some(quality) = details.get("grade").and_then(|x| x.parse::<u8>().ok())
+        .filter(|x| *x <= 5) else {
+        return (error("invalid grade"), db);
+    };
+    let row = sqlx::query("SELECT ease, review_interval, repetitions FROM reviews
+                           WHERE learner = ? AND dictionary = ? AND word = ?")
+        .bind(learner).bind(&dict.meta.name).bind(word)
+        .fetch_optional(&mut **db).await.unwrap();
+    let Some(row) = row else { return (error("not under review"), db) };
+    let review = Review {
+        ease: row.get(0),
+        interval: row.get::<i32, _>(1) as u32,
+        repetitions: row.get::<i32, _>(2) as u32,
+    }.grade(quality);
+    sqlx::query("UPDATE reviews SET ease = ?, review_interval = ?, repetitions = ?,
+                 due = NOW() + INTERVAL ? DAY
+                 WHERE learner = ? AND dictionary = ? AND word = ?")
+        .bind(review.ease).bind(review.interval).bind(review.repetitions)
+        .bind(review.interval)
+        .bind(learner).bind(&dict.meta.name).bind(word)
+        .execute(&mut **db).await.unwrap();
+    let details = HashMap::from([
+        ("interval".to_string(), review.interval.to_string()),
+        ("ease".to_string(), review.ease.to_string()),
+    ]);
+    (Json(Message { session: 0, details }), db)
+}
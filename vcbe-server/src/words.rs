@@ -0,0 +1,123 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use once_cell::sync::Lazy;
+use rocket::tokio::sync::RwLock;
+use rocket_db_pools::sqlx;
+use rocket_db_pools::sqlx::Row;
+use vcbe_core::{BkTree, Definition, InclusionIndex};
+use crate::{BaseConn, WithConn};
+use crate::common::Dictionary;
+
+/// Spellings of a dictionary indexed by id, with what searches go through
+struct Spellings {
+    words: Vec<String>,
+    /// Ids in the order of their lowercased spellings, for prefix search
+    sorted: Vec<usize>,
+    lowercase: Vec<String>,
+    /// Lowercased spellings, for fuzzy search
+    tree: BkTree,
+    /// Lowercased spellings ranked by id, for searching words containing the query
+    inclusion: InclusionIndex,
+}
+
+/// Spellings of each dictionary, keyed by the words table
+static SPELLINGS: Lazy<RwLock<HashMap<String, Arc<Spellings>>>> =
+    Lazy::new(|| RwLock::new(HashMap::new()));
+
+/// Most results returned by a search
+pub const SEARCH_LIMIT: usize = 20;
+
+/// Fuzzy matches are at most this many edits away
+const FUZZY_DISTANCE: usize = 2;
+
+async fn spellings(dict: &Dictionary, mut db: BaseConn) -> WithConn<Arc<Spellings>> {
+    if let Some(spellings) = SPELLINGS.read().await.get(&dict.words) {
+        return (spellings.clone(), db);
+    }
+    let words: Vec<String> = sqlx::query(&format!("SELECT word FROM {} ORDER BY id", dict.words))
+        .fetch_all(&mut **db).await.unwrap()
+        .iter()
+        .map(|row| row.get(0))
+        .collect();
+    let lowercase = words.iter().map(|x| x.to_lowercase()).collect::<Vec<_>>();
+    let mut sorted = (0..words.len()).collect::<Vec<_>>();
+    sorted.sort_by(|x, y| lowercase[*x].cmp(&lowercase[*y]));
+    let tree = BkTree::new(lowercase.iter().map(|x| x.chars().collect()).collect());
+    // ids follow frequency, so lower ids rank first
+    let ranks = (0..words.len() as u32).rev().collect::<Vec<_>>();
+    let inclusion = InclusionIndex::new(&lowercase, &ranks);
+    let spellings = Arc::new(Spellings { words, sorted, lowercase, tree, inclusion });
+    SPELLINGS.write().await.insert(dict.words.clone(), spellings.clone());
+    (spellings, db)
+}
+
+/// Words starting with `query`, then words containing it, then words within a few edits of
+/// it, most frequent first in each
+pub async fn search(query: &str, dict: &Dictionary, db: BaseConn) -> WithConn<Vec<String>> {
+    let (spellings, db) = spellings(dict, db).await;
+    let query = query.trim().to_lowercase();
+    if query.is_empty() { return (Vec::new(), db); }
+    let Spellings { words, sorted, lowercase, tree, inclusion } = &*spellings;
+    let begin = sorted.partition_point(|x| lowercase[*x] < query);
+    let end = begin + sorted[begin..].partition_point(|x| lowercase[*x].starts_with(&query));
+    let mut found = sorted[begin..end].to_vec();
+    found.sort_unstable();
+    found.truncate(SEARCH_LIMIT);
+    if found.len() < SEARCH_LIMIT {
+        let containing = inclusion.containing(&query, SEARCH_LIMIT + found.len()).into_iter()
+            .filter(|x| !found.contains(x))
+            .take(SEARCH_LIMIT - found.len())
+            .collect::<Vec<_>>();
+        found.extend(containing);
+    }
+    if found.len() < SEARCH_LIMIT {
+        let chars = query.chars().collect::<Vec<_>>();
+        let fuzzy = tree.find(&chars, FUZZY_DISTANCE).into_iter()
+            .map(|(i, _)| i)
+            .filter(|x| !found.contains(x))
+            .take(SEARCH_LIMIT - found.len())
+            .collect::<Vec<_>>();
+        found.extend(fuzzy);
+    }
+    (found.into_iter().map(|x| words[x].clone()).collect(), db)
+}
+
+/// The most frequent entry spelled `word` with its related words resolved to spellings, the
+/// parts of speech of its definitions in "pos" and their texts in "desc"
+pub async fn lookup(
+    word: &str, dict: &Dictionary, db: BaseConn
+) -> WithConn<Option<HashMap<String, String>>> {
+    let (spellings, mut db) = spellings(dict, db).await;
+    let row = sqlx::query(&format!(
+        "SELECT id, freq, lv, des, {}, {}, sim, incl, incl_rev, {} FROM {}
+         WHERE word = ? ORDER BY id LIMIT 1",
+        dict.column("head"), dict.column("exam"), dict.column("sound"), dict.words))
+        .bind(word)
+        .fetch_optional(&mut **db).await.unwrap();
+    let Some(row) = row else { return (None, db) };
+    let desc = row.get::<String, _>(3).split(";;;")
+        .filter(|x| !x.is_empty())
+        .map(Definition::parse)
+        .collect::<Vec<_>>();
+    let join = |f: fn(&Definition) -> &str| desc.iter().map(f).collect::<Vec<_>>().join(";;;");
+    let spell = |ids: String| ids.split(',')
+        .filter_map(|x| x.parse::<usize>().ok())
+        .filter_map(|x| spellings.words.get(x).cloned())
+        .collect::<Vec<_>>()
+        .join(";;;");
+    let entry = HashMap::from([
+        ("word".to_string(), word.to_string()),
+        ("id".to_string(), row.get::<i32, _>(0).to_string()),
+        ("freq".to_string(), row.get::<i32, _>(1).to_string()),
+        ("lv".to_string(), row.get::<i32, _>(2).to_string()),
+        ("pos".to_string(), join(|x| &x.pos)),
+        ("desc".to_string(), join(|x| &x.text)),
+        ("head".to_string(), row.get(4)),
+        ("exam".to_string(), row.get(5)),
+        ("sim".to_string(), spell(row.get(6))),
+        ("incl".to_string(), spell(row.get(7))),
+        ("incl_rev".to_string(), spell(row.get(8))),
+        ("sound".to_string(), spell(row.get(9))),
+    ]);
+    (Some(entry), db)
+}